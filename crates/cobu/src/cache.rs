@@ -0,0 +1,116 @@
+use std::{
+    collections::BTreeMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_json::{json, Value};
+
+struct CacheEntry {
+    hash: u64,
+    sources: Vec<PathBuf>,
+}
+
+/// Hashes of the inputs of the bundles in an output directory, so bundles whose sources,
+/// options, toolchain and cobu version did not change can be skipped.
+pub(crate) struct BundleCache {
+    path: PathBuf,
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+/// Output of `rustc -vV`, since the bundles are checked and formatted with that toolchain.
+pub(crate) fn rustc_version() -> String {
+    std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// Hashes `key` together with the contents of `sources`, or `None` if a source can't be read
+/// anymore.
+fn hash_sources(key: &str, sources: &[PathBuf]) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    key.hash(&mut hasher);
+    for source in sources {
+        source.hash(&mut hasher);
+        std::fs::read(source).ok()?.hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+fn parse_entry(value: &Value) -> Option<CacheEntry> {
+    let hash = u64::from_str_radix(value["hash"].as_str()?, 16).ok()?;
+    let sources = value["sources"]
+        .as_array()?
+        .iter()
+        .map(|s| s.as_str().map(PathBuf::from))
+        .collect::<Option<_>>()?;
+    Some(CacheEntry { hash, sources })
+}
+
+impl BundleCache {
+    /// Loads the cache of the bundles of `package` in `out_dir`. A missing or unreadable cache
+    /// is treated as empty. It's kept under `target_dir` rather than next to the submissions,
+    /// in a file of its own for each package and `out_dir` since the build scripts of several
+    /// packages can run at once.
+    pub(crate) fn load(target_dir: &Path, package: &str, out_dir: &Path) -> Self {
+        let mut hasher = DefaultHasher::new();
+        std::path::absolute(out_dir)
+            .unwrap_or_else(|_| out_dir.to_path_buf())
+            .hash(&mut hasher);
+        let path = target_dir
+            .join("cobu")
+            .join(format!("cache-{package}-{:016x}.json", hasher.finish()));
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .and_then(|v| {
+                v.as_object()?
+                    .iter()
+                    .map(|(bin, entry)| Some((bin.clone(), parse_entry(entry)?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    /// Returns the sources of the bundle of `bin` if none of them changed since it was made
+    /// with the same `key`.
+    pub(crate) fn fresh_sources(&self, bin: &str, key: &str) -> Option<&[PathBuf]> {
+        let entry = self.entries.get(bin)?;
+        (hash_sources(key, &entry.sources)? == entry.hash).then_some(entry.sources.as_slice())
+    }
+
+    pub(crate) fn insert(&mut self, bin: String, key: &str, sources: Vec<PathBuf>) {
+        match hash_sources(key, &sources) {
+            Some(hash) => {
+                self.entries.insert(bin, CacheEntry { hash, sources });
+            }
+            None => {
+                self.entries.remove(&bin);
+            }
+        }
+    }
+
+    pub(crate) fn save(&self) -> anyhow::Result<()> {
+        let value: serde_json::Map<String, Value> = self
+            .entries
+            .iter()
+            .map(|(bin, entry)| {
+                let entry = json!({
+                    "hash": format!("{:016x}", entry.hash),
+                    "sources": entry.sources,
+                });
+                (bin.clone(), entry)
+            })
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&value)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
@@ -0,0 +1,402 @@
+use std::{ops::Range, str::FromStr};
+
+use cargo_platform::{Cfg, CfgExpr};
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use syn::{punctuated::Punctuated, spanned::Spanned, visit::Visit, Attribute};
+
+use crate::expand::replace_ranges;
+
+/// The cfg set of the judge the bundle is submitted to: a release build on 64-bit Windows,
+/// like Codeforces, with no features enabled.
+pub fn default_target_cfg() -> Vec<Cfg> {
+    [
+        "windows",
+        "target_os = \"windows\"",
+        "target_family = \"windows\"",
+        "target_arch = \"x86_64\"",
+        "target_pointer_width = \"64\"",
+        "target_endian = \"little\"",
+        "panic = \"unwind\"",
+    ]
+    .into_iter()
+    .map(|s| Cfg::from_str(s).expect("valid default cfg"))
+    .collect()
+}
+
+/// Adds `overrides` to `base`. A key-value override like `target_os = "linux"` replaces all the
+/// values of that key in `base`. Overriding `target_family` also replaces the `unix` and
+/// `windows` shorthands.
+pub fn override_target_cfg(base: Vec<Cfg>, overrides: &[Cfg]) -> Vec<Cfg> {
+    let is_overridden_key = |key: &str| {
+        overrides
+            .iter()
+            .any(|o| matches!(o, Cfg::KeyPair(override_key, _) if override_key == key))
+    };
+    let family_shorthands: Vec<Cfg> = overrides
+        .iter()
+        .filter_map(|o| match o {
+            Cfg::KeyPair(key, family) if key == "target_family" => Cfg::from_str(family).ok(),
+            _ => None,
+        })
+        .filter(|family| matches!(family, Cfg::Name(name) if name == "unix" || name == "windows"))
+        .collect();
+    base.into_iter()
+        .filter(|cfg| match cfg {
+            Cfg::KeyPair(key, _) => !is_overridden_key(key.as_str()),
+            Cfg::Name(name) if name == "unix" || name == "windows" => {
+                !is_overridden_key("target_family")
+            }
+            Cfg::Name(_) => true,
+        })
+        .chain(family_shorthands)
+        .chain(overrides.iter().cloned())
+        .collect()
+}
+
+macro_rules! attrs_of {
+    ($node:expr, $ty:ident, [$($variant:ident),* $(,)?]) => {
+        match $node {
+            $(syn::$ty::$variant(node) => node.attrs.as_slice(),)*
+            _ => &[],
+        }
+    };
+}
+
+fn item_attrs(item: &syn::Item) -> &[Attribute] {
+    attrs_of!(
+        item,
+        Item,
+        [
+            Const,
+            Enum,
+            ExternCrate,
+            Fn,
+            ForeignMod,
+            Impl,
+            Macro,
+            Mod,
+            Static,
+            Struct,
+            Trait,
+            TraitAlias,
+            Type,
+            Union,
+            Use,
+        ]
+    )
+}
+
+fn impl_item_attrs(item: &syn::ImplItem) -> &[Attribute] {
+    attrs_of!(item, ImplItem, [Const, Fn, Type, Macro])
+}
+
+fn trait_item_attrs(item: &syn::TraitItem) -> &[Attribute] {
+    attrs_of!(item, TraitItem, [Const, Fn, Type, Macro])
+}
+
+fn foreign_item_attrs(item: &syn::ForeignItem) -> &[Attribute] {
+    attrs_of!(item, ForeignItem, [Fn, Static, Type, Macro])
+}
+
+fn expr_attrs(expr: &syn::Expr) -> &[Attribute] {
+    attrs_of!(
+        expr,
+        Expr,
+        [
+            Array, Assign, Async, Await, Binary, Block, Break, Call, Cast, Closure, Const,
+            Continue, Field, ForLoop, Group, If, Index, Infer, Let, Lit, Loop, Macro, Match,
+            MethodCall, Paren, Path, Range, RawAddr, Reference, Repeat, Return, Struct, Try,
+            TryBlock, Tuple, Unary, Unsafe, While, Yield,
+        ]
+    )
+}
+
+/// Splits a token stream at its top level commas.
+fn split_commas(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    for token in tokens {
+        match &token {
+            TokenTree::Punct(p) if p.as_char() == ',' => parts.push(Vec::new()),
+            _ => parts.last_mut().unwrap().push(token),
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn tokens_range(tokens: &[TokenTree]) -> Range<usize> {
+    tokens.first().unwrap().span().byte_range().start
+        ..tokens.last().unwrap().span().byte_range().end
+}
+
+struct CfgVisitor<'a> {
+    src: &'a str,
+    target_cfg: &'a [Cfg],
+    output_edits: Vec<(Range<usize>, String)>,
+}
+
+/// Evaluates `expr` against `target_cfg`, or returns `None` if its value depends on a key that
+/// `target_cfg` doesn't define, like `target_feature`. Features are always defined, since
+/// `target_cfg` lists all the enabled ones.
+fn eval_cfg_expr(expr: &CfgExpr, target_cfg: &[Cfg]) -> Option<bool> {
+    let eval_all = |exprs: &[CfgExpr]| -> Vec<Option<bool>> {
+        exprs.iter().map(|e| eval_cfg_expr(e, target_cfg)).collect()
+    };
+    match expr {
+        CfgExpr::Not(e) => eval_cfg_expr(e, target_cfg).map(|value| !value),
+        CfgExpr::All(exprs) => {
+            let values = eval_all(exprs);
+            if values.contains(&Some(false)) {
+                Some(false)
+            } else if values.contains(&None) {
+                None
+            } else {
+                Some(true)
+            }
+        }
+        CfgExpr::Any(exprs) => {
+            let values = eval_all(exprs);
+            if values.contains(&Some(true)) {
+                Some(true)
+            } else if values.contains(&None) {
+                None
+            } else {
+                Some(false)
+            }
+        }
+        CfgExpr::Value(Cfg::KeyPair(key, _))
+            if key.as_str() != "feature"
+                && !target_cfg
+                    .iter()
+                    .any(|cfg| matches!(cfg, Cfg::KeyPair(defined, _) if defined == key)) =>
+        {
+            None
+        }
+        CfgExpr::Value(cfg) => Some(target_cfg.contains(cfg)),
+    }
+}
+
+impl CfgVisitor<'_> {
+    /// Evaluates a cfg predicate, or returns `None` if it can't be understood.
+    fn eval(&self, predicate: &str) -> Option<bool> {
+        let expr = CfgExpr::from_str(predicate).ok()?;
+        eval_cfg_expr(&expr, self.target_cfg)
+    }
+
+    /// Returns the attributes a true `cfg_attr` expands to, evaluating the `cfg` and `cfg_attr`
+    /// among them too, or `None` if one of them is a false `cfg`.
+    fn expand_cfg_attr(&self, attrs: &[Vec<TokenTree>], style: &str) -> Option<Vec<String>> {
+        let mut expanded = Vec::new();
+        for attr in attrs {
+            let original = format!("{style}[{}]", &self.src[tokens_range(attr)]);
+            let (name, args) = match attr.as_slice() {
+                [TokenTree::Ident(name), TokenTree::Group(group)]
+                    if group.delimiter() == Delimiter::Parenthesis =>
+                {
+                    (name.to_string(), group.stream())
+                }
+                _ => {
+                    expanded.push(original);
+                    continue;
+                }
+            };
+            if name == "cfg" {
+                match self.eval(&args.to_string()) {
+                    Some(true) => (),
+                    Some(false) => return None,
+                    None => expanded.push(original),
+                }
+            } else if name == "cfg_attr" {
+                let parts = split_commas(args);
+                let Some((predicate, attrs)) = parts.split_first() else {
+                    expanded.push(original);
+                    continue;
+                };
+                let predicate: TokenStream = predicate.iter().cloned().collect();
+                match self.eval(&predicate.to_string()) {
+                    Some(true) => expanded.extend(self.expand_cfg_attr(attrs, style)?),
+                    Some(false) => (),
+                    None => expanded.push(original),
+                }
+            } else {
+                expanded.push(original);
+            }
+        }
+        Some(expanded)
+    }
+
+    /// Handles the `cfg` and `cfg_attr` attributes of a node spanning `range`, and returns
+    /// whether the node is kept.
+    fn visit_cfg_attrs(&mut self, attrs: &[Attribute], range: Range<usize>) -> bool {
+        let mut edits = Vec::new();
+        for attr in attrs {
+            let syn::Meta::List(meta_list) = &attr.meta else {
+                continue;
+            };
+            if attr.path().is_ident("cfg") {
+                match self.eval(&meta_list.tokens.to_string()) {
+                    Some(true) => edits.push((attr.span().byte_range(), String::new())),
+                    Some(false) => {
+                        self.output_edits.push((range, String::new()));
+                        return false;
+                    }
+                    None => (),
+                }
+            } else if attr.path().is_ident("cfg_attr") {
+                let parts = split_commas(meta_list.tokens.clone());
+                let Some((predicate, attrs)) = parts.split_first() else {
+                    continue;
+                };
+                let predicate: TokenStream = predicate.iter().cloned().collect();
+                let style = match attr.style {
+                    syn::AttrStyle::Outer => "#",
+                    syn::AttrStyle::Inner(_) => "#!",
+                };
+                match self.eval(&predicate.to_string()) {
+                    Some(true) => match self.expand_cfg_attr(attrs, style) {
+                        Some(attrs) => edits.push((attr.span().byte_range(), attrs.join(" "))),
+                        None => {
+                            self.output_edits.push((range, String::new()));
+                            return false;
+                        }
+                    },
+                    Some(false) => edits.push((attr.span().byte_range(), String::new())),
+                    None => (),
+                }
+            }
+        }
+        self.output_edits.extend(edits);
+        true
+    }
+
+    /// Like [`Self::visit_cfg_attrs`] for elements of a comma separated list, which take their
+    /// trailing comma with them when removed.
+    fn visit_punctuated<T: Spanned, P: Spanned>(
+        &mut self,
+        punctuated: &Punctuated<T, P>,
+        attrs: impl Fn(&T) -> &[Attribute],
+        mut visit: impl FnMut(&mut Self, &T),
+    ) {
+        for pair in punctuated.pairs() {
+            let (node, punct) = pair.into_tuple();
+            let mut range = node.span().byte_range();
+            if let Some(punct) = punct {
+                range.end = punct.span().byte_range().end;
+                // Take the line break along too, or rustfmt leaves an empty line behind.
+                let whitespace =
+                    self.src[range.end..].len() - self.src[range.end..].trim_start().len();
+                range.end += whitespace;
+            }
+            if self.visit_cfg_attrs(attrs(node), range) {
+                visit(self, node);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for CfgVisitor<'_> {
+    fn visit_file(&mut self, i: &'ast syn::File) {
+        if !self.visit_cfg_attrs(&i.attrs, 0..self.src.len()) {
+            return;
+        }
+        for item in &i.items {
+            self.visit_item(item);
+        }
+    }
+
+    fn visit_item(&mut self, i: &'ast syn::Item) {
+        if self.visit_cfg_attrs(item_attrs(i), i.span().byte_range()) {
+            syn::visit::visit_item(self, i);
+        }
+    }
+
+    fn visit_impl_item(&mut self, i: &'ast syn::ImplItem) {
+        if self.visit_cfg_attrs(impl_item_attrs(i), i.span().byte_range()) {
+            syn::visit::visit_impl_item(self, i);
+        }
+    }
+
+    fn visit_trait_item(&mut self, i: &'ast syn::TraitItem) {
+        if self.visit_cfg_attrs(trait_item_attrs(i), i.span().byte_range()) {
+            syn::visit::visit_trait_item(self, i);
+        }
+    }
+
+    fn visit_foreign_item(&mut self, i: &'ast syn::ForeignItem) {
+        if self.visit_cfg_attrs(foreign_item_attrs(i), i.span().byte_range()) {
+            syn::visit::visit_foreign_item(self, i);
+        }
+    }
+
+    fn visit_stmt(&mut self, i: &'ast syn::Stmt) {
+        let attrs = match i {
+            syn::Stmt::Local(local) => local.attrs.as_slice(),
+            syn::Stmt::Macro(stmt_macro) => stmt_macro.attrs.as_slice(),
+            // Only expressions in statement position can be removed, a tail expression can't.
+            syn::Stmt::Expr(expr, Some(_)) => expr_attrs(expr),
+            syn::Stmt::Expr(_, None) | syn::Stmt::Item(_) => &[],
+        };
+        if self.visit_cfg_attrs(attrs, i.span().byte_range()) {
+            syn::visit::visit_stmt(self, i);
+        }
+    }
+
+    fn visit_arm(&mut self, i: &'ast syn::Arm) {
+        if self.visit_cfg_attrs(&i.attrs, i.span().byte_range()) {
+            syn::visit::visit_arm(self, i);
+        }
+    }
+
+    fn visit_fields_named(&mut self, i: &'ast syn::FieldsNamed) {
+        self.visit_punctuated(&i.named, |f| &f.attrs, |v, f| v.visit_field(f));
+    }
+
+    fn visit_fields_unnamed(&mut self, i: &'ast syn::FieldsUnnamed) {
+        self.visit_punctuated(&i.unnamed, |f| &f.attrs, |v, f| v.visit_field(f));
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast syn::ItemEnum) {
+        self.visit_generics(&i.generics);
+        self.visit_punctuated(
+            &i.variants,
+            |v| &v.attrs,
+            |v, variant| v.visit_variant(variant),
+        );
+    }
+
+    fn visit_expr_struct(&mut self, i: &'ast syn::ExprStruct) {
+        self.visit_path(&i.path);
+        self.visit_punctuated(&i.fields, |f| &f.attrs, |v, f| v.visit_field_value(f));
+        if let Some(rest) = &i.rest {
+            self.visit_expr(rest);
+        }
+    }
+
+    fn visit_pat_struct(&mut self, i: &'ast syn::PatStruct) {
+        self.visit_path(&i.path);
+        self.visit_punctuated(&i.fields, |f| &f.attrs, |v, f| v.visit_field_pat(f));
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        if i.path.is_ident("cfg") {
+            if let Some(value) = self.eval(&i.tokens.to_string()) {
+                self.output_edits
+                    .push((i.span().byte_range(), value.to_string()));
+            }
+        }
+    }
+}
+
+/// Evaluates `#[cfg]`, `#[cfg_attr]` and `cfg!` against `target_cfg`, removing the code that
+/// would not be compiled for the target and the cfg attributes of the code that would.
+pub fn strip_cfgs(src: String, target_cfg: &[Cfg]) -> anyhow::Result<String> {
+    let ast = syn::parse_file(&src)?;
+    let mut visitor = CfgVisitor {
+        src: &src,
+        target_cfg,
+        output_edits: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+    let edits = visitor.output_edits;
+    Ok(replace_ranges(&src, edits))
+}
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel};
+
+use crate::{
+    dead_code::{rustc_command_diagnostics, rustc_diagnostics},
+    rust_version::newer_features,
+};
+
+/// Drops whitespace and the `pub(crate)` that bundling turns `pub` into, so lines of the
+/// bundle can be compared to lines of the files it was made from.
+fn normalize_line(line: &str) -> String {
+    line.replace("pub(crate)", "pub")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// Finds the file and line number a line of the bundle comes from, or `None` if no line of
+/// `sources` or more than one looks like it.
+fn find_source_line(line: &str, sources: &[PathBuf]) -> Option<(PathBuf, usize)> {
+    let line = normalize_line(line);
+    // Lines like `}` are everywhere.
+    if line.len() < 4 {
+        return None;
+    }
+    let mut matches = sources.iter().flat_map(|source| {
+        let contents = std::fs::read_to_string(source).unwrap_or_default();
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, source_line)| normalize_line(source_line) == line)
+            .map(|(index, _)| (source.clone(), index + 1))
+            .collect::<Vec<_>>()
+    });
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first)
+}
+
+fn source_note(line_number: usize, src: &str, sources: &[PathBuf]) -> String {
+    match src
+        .lines()
+        .nth(line_number - 1)
+        .and_then(|line| find_source_line(line, sources))
+    {
+        Some((source, line_number)) => {
+            format!("\n  = note: comes from {}:{line_number}", source.display())
+        }
+        None => "\n  = note: the line it comes from is unknown".to_string(),
+    }
+}
+
+/// Renders the errors among `diagnostics` of the bundle at `path` with contents `src`.
+fn render_errors(
+    diagnostics: Vec<Diagnostic>,
+    path: &Path,
+    src: &str,
+    sources: &[PathBuf],
+) -> Vec<String> {
+    diagnostics
+        .into_iter()
+        .filter(|d| d.level == DiagnosticLevel::Error)
+        // The summary at the end isn't an error of its own.
+        .filter(|d| !d.message.starts_with("aborting due to"))
+        .map(|d| {
+            let rendered = d.rendered.unwrap_or(d.message);
+            let mut error = rendered
+                .trim_end()
+                .replace("<anon>", &path.display().to_string());
+            if let Some(span) = d.spans.iter().find(|s| s.is_primary) {
+                error.push_str(&source_note(span.line_start, src, sources));
+            }
+            error
+        })
+        .collect()
+}
+
+/// Compiles the bundle at `path` with contents `src`, and fails with the compiler errors if it
+/// doesn't build, pointing each one to the line of `sources` it probably comes from.
+pub(crate) fn check_bundle(
+    path: &Path,
+    src: &str,
+    edition: &str,
+    sources: &[PathBuf],
+) -> anyhow::Result<()> {
+    let errors = render_errors(rustc_diagnostics(src, edition)?, path, src, sources);
+    anyhow::ensure!(
+        errors.is_empty(),
+        "Bundle {} doesn't compile:\n\n{}",
+        path.display(),
+        errors.join("\n\n")
+    );
+    Ok(())
+}
+
+fn is_toolchain_installed(rust_version: &str) -> bool {
+    std::process::Command::new("rustup")
+        .args(["toolchain", "list"])
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+                line.strip_prefix(rust_version)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', ' ']))
+            })
+        })
+}
+
+/// Checks that the bundle at `path` with contents `src` works with the `rust_version` of the
+/// judge. If that toolchain is installed, the bundle is compiled with it, and it fails with the
+/// errors. Otherwise the std items and syntax the bundle uses are looked up in a table of
+/// the versions that stabilized them, and the newer ones are reported as warnings. A missing
+/// toolchain isn't an error even if the version was given explicitly: the table can't tell a
+/// std method from a method of the same name of another trait, so it can only warn.
+pub(crate) fn check_rust_version(
+    path: &Path,
+    src: &str,
+    edition: &str,
+    rust_version: &str,
+    sources: &[PathBuf],
+    cargo_warnings: bool,
+) -> anyhow::Result<()> {
+    if is_toolchain_installed(rust_version) {
+        let mut rustc = std::process::Command::new("rustup");
+        rustc.args(["run", rust_version, "rustc"]);
+        let output = std::env::temp_dir().join(format!(
+            "cobu-{}-{}.rmeta",
+            std::process::id(),
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        ));
+        let diagnostics = rustc_command_diagnostics(rustc, &output, src, edition);
+        let _ = std::fs::remove_file(&output);
+        let errors = render_errors(diagnostics?, path, src, sources);
+        anyhow::ensure!(
+            errors.is_empty(),
+            "Bundle {} doesn't compile with Rust {rust_version}:\n\n{}",
+            path.display(),
+            errors.join("\n\n")
+        );
+        return Ok(());
+    }
+    warn_newer_features(path, src, rust_version, sources, cargo_warnings)
+}
+
+/// Repeats the warnings [`check_rust_version`] gave for a bundle taken from the cache, which
+/// would otherwise only show up the first time it was made. With the toolchain installed,
+/// the bundle compiled with it back then, so there is nothing to repeat.
+pub(crate) fn check_cached_rust_version(
+    path: &Path,
+    rust_version: &str,
+    sources: &[PathBuf],
+    cargo_warnings: bool,
+) -> anyhow::Result<()> {
+    if is_toolchain_installed(rust_version) {
+        return Ok(());
+    }
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    warn_newer_features(path, &src, rust_version, sources, cargo_warnings)
+}
+
+/// Warns about the std items and syntax of the bundle that are newer than `rust_version`.
+/// Cargo hides what a build script prints to stderr unless it fails, so with
+/// `cargo_warnings` the warnings are printed as `cargo:warning` lines instead.
+fn warn_newer_features(
+    path: &Path,
+    src: &str,
+    rust_version: &str,
+    sources: &[PathBuf],
+    cargo_warnings: bool,
+) -> anyhow::Result<()> {
+    for feature in newer_features(src, rust_version)? {
+        let start = feature.span.start();
+        let needs = if feature.is_possible_match {
+            "might need"
+        } else {
+            "needs"
+        };
+        let warning = format!(
+            "{} {needs} Rust {}, but the judge runs {rust_version}\n  --> {}:{}:{}{}",
+            feature.description,
+            feature.version,
+            path.display(),
+            start.line,
+            start.column + 1,
+            source_note(start.line, src, sources)
+        );
+        if cargo_warnings {
+            for line in warning.lines() {
+                println!("cargo:warning={line}");
+            }
+        } else {
+            eprintln!("warning: {warning}");
+        }
+    }
+    Ok(())
+}
@@ -1,24 +1,59 @@
-use std::{collections::BTreeSet, io::Write, ops::Range};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 
-use cargo_metadata::diagnostic::{Diagnostic, DiagnosticSpan};
-use proc_macro2::Span;
-use quote::quote;
-use syn::{spanned::Spanned, token, visit::Visit, Ident};
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel, DiagnosticSpan};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    punctuated::{Pair, Punctuated},
+    spanned::Spanned,
+    visit::Visit,
+    Ident,
+};
 
-fn rustc_diagnostics(src: &str) -> anyhow::Result<Vec<Diagnostic>> {
-    let mut command = std::process::Command::new("rustc")
+use crate::{
+    expand::replace_ranges,
+    judge::DEFAULT_EDITION,
+    macros::invoked_macro_names,
+    minify::is_cosmetic_attribute_path,
+    reach,
+    scope::{ItemPath, Scopes},
+};
+
+pub(crate) fn rustc_diagnostics(src: &str, edition: &str) -> anyhow::Result<Vec<Diagnostic>> {
+    rustc_command_diagnostics(
+        std::process::Command::new("rustc"),
+        Path::new("-"),
+        src,
+        edition,
+    )
+}
+
+/// Like [`rustc_diagnostics`] with a command that runs rustc, like `rustup run 1.70.0 rustc`,
+/// writing the metadata to `output`. Older versions can't write it to stdout.
+pub(crate) fn rustc_command_diagnostics(
+    mut rustc: std::process::Command,
+    output: &Path,
+    src: &str,
+    edition: &str,
+) -> anyhow::Result<Vec<Diagnostic>> {
+    let mut command = rustc
         .args([
-            "--edition", "2024",
+            "--edition", edition,
             "--error-format=json",
-            "-C",
-            "debuginfo=none",
-            // "-C", "linkargs=/DEBUG:NONE", // TODO: Figure out how to not generate PDB with MSVC
+            // Lints run before codegen, so there is no need to build and link anything
+            "--emit=metadata",
             "-o",
-            "-",
-            "-",
         ])
+        .arg(output)
+        .arg("-")
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::piped())
@@ -54,20 +89,60 @@ fn rustc_diagnostics(src: &str) -> anyhow::Result<Vec<Diagnostic>> {
 
 struct DeadIdentifierVisitor {
     dead_code_diagnostic_spans: Vec<DiagnosticSpan>,
-    output_dead_struct_identifiers: Vec<Ident>,
-    output_dead_fn_identifiers: Vec<Ident>,
-    output_dead_trait_identifiers: Vec<Ident>,
-    output_dead_use_identifiers: Vec<Ident>,
+    /// Names of the macros invoked anywhere, since rustc never reports `#[macro_export]`
+    /// macros as unused
+    invoked_macros: BTreeSet<String>,
+    /// Module of the items being visited
+    module: ItemPath,
+    output_dead_items: BTreeSet<ItemPath>,
+    output_dead_use_tree_ranges: Vec<Range<usize>>,
+    output_dead_impl_item_ranges: Vec<Range<usize>>,
+    output_dead_macro_ranges: Vec<Range<usize>>,
+    output_dead_fields: Vec<DeadMember>,
+    output_dead_variants: Vec<DeadMember>,
+    output_field_name_counts: BTreeMap<Ident, usize>,
+    output_type_name_counts: BTreeMap<Ident, usize>,
 }
 
 impl DeadIdentifierVisitor {
-    fn new(dead_code_diagnostic_spans: Vec<DiagnosticSpan>) -> Self {
+    fn new(
+        dead_code_diagnostic_spans: Vec<DiagnosticSpan>,
+        invoked_macros: BTreeSet<String>,
+    ) -> Self {
         Self {
             dead_code_diagnostic_spans,
-            output_dead_struct_identifiers: Vec::new(),
-            output_dead_fn_identifiers: Vec::new(),
-            output_dead_trait_identifiers: Vec::new(),
-            output_dead_use_identifiers: Vec::new(),
+            invoked_macros,
+            module: Vec::new(),
+            output_dead_items: BTreeSet::new(),
+            output_dead_use_tree_ranges: Vec::new(),
+            output_dead_impl_item_ranges: Vec::new(),
+            output_dead_macro_ranges: Vec::new(),
+            output_dead_fields: Vec::new(),
+            output_dead_variants: Vec::new(),
+            output_field_name_counts: BTreeMap::new(),
+            output_type_name_counts: BTreeMap::new(),
+        }
+    }
+
+    fn push_dead_item(&mut self, ident: &Ident) {
+        let mut path = self.module.clone();
+        path.push(ident.to_string());
+        self.output_dead_items.insert(path);
+    }
+
+    fn count_type_name(&mut self, ident: &Ident) {
+        *self
+            .output_type_name_counts
+            .entry(ident.clone())
+            .or_default() += 1;
+    }
+
+    fn count_field_names(&mut self, fields: &syn::Fields) {
+        for ident in fields.iter().filter_map(|f| f.ident.as_ref()) {
+            *self
+                .output_field_name_counts
+                .entry(ident.clone())
+                .or_default() += 1;
         }
     }
 
@@ -107,116 +182,903 @@ impl DeadIdentifierVisitor {
 }
 
 impl<'ast> Visit<'ast> for DeadIdentifierVisitor {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+
     fn visit_item_struct(&mut self, i: &'ast syn::ItemStruct) {
+        self.count_type_name(&i.ident);
+        self.count_field_names(&i.fields);
         if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
-            self.output_dead_struct_identifiers.push(i.ident.clone());
+            self.push_dead_item(&i.ident);
+            return;
+        }
+        // The layout of `#[repr]` structs matters, and fields only read by a derived `Debug`
+        // still show up in the output.
+        if has_attr(&i.attrs, "repr") || derives(&i.attrs, "Debug") {
+            return;
+        }
+        let syn::Fields::Named(fields) = &i.fields else {
+            // Removing a tuple field would shift the index of the fields after it
+            return;
+        };
+        let dead_fields: Vec<DeadMember> = fields
+            .named
+            .pairs()
+            .filter(|pair| {
+                let ident = pair.value().ident.as_ref().unwrap();
+                self.is_dead_code(Some(ident), &ident.span())
+            })
+            .map(|pair| DeadMember::new(&i.ident, pair.value().ident.as_ref().unwrap(), &pair))
+            .collect();
+        let remaining_types = fields
+            .named
+            .iter()
+            .filter(|f| {
+                !dead_fields
+                    .iter()
+                    .any(|d| Some(&d.ident) == f.ident.as_ref())
+            })
+            .map(|f| f.ty.to_token_stream());
+        if uses_all_generics(&i.generics, remaining_types) {
+            self.output_dead_fields.extend(dead_fields);
+        }
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast syn::ItemEnum) {
+        self.count_type_name(&i.ident);
+        for variant in &i.variants {
+            self.count_field_names(&variant.fields);
+        }
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.push_dead_item(&i.ident);
+            return;
+        }
+        // Removing a variant changes the discriminants of the ones after it, which can be
+        // observed by casting a fieldless enum or through `#[repr]`.
+        let is_fieldless = i.variants.iter().all(|v| v.fields.is_empty());
+        if has_attr(&i.attrs, "repr") || i.variants.iter().any(|v| v.discriminant.is_some()) {
+            return;
+        }
+        let variant_count = i.variants.len();
+        let dead_variants: Vec<DeadMember> = i
+            .variants
+            .pairs()
+            .enumerate()
+            .filter(|(index, pair)| {
+                let variant = pair.value();
+                (!is_fieldless || index + 1 == variant_count)
+                    && !has_attr(&variant.attrs, "default")
+                    && self.is_dead_code(Some(&variant.ident), &variant.ident.span())
+            })
+            .map(|(_, pair)| DeadMember::new(&i.ident, &pair.value().ident, &pair))
+            .collect();
+        let remaining_types = i
+            .variants
+            .iter()
+            .filter(|v| !dead_variants.iter().any(|d| d.ident == v.ident))
+            .map(|v| v.fields.to_token_stream());
+        if uses_all_generics(&i.generics, remaining_types) {
+            self.output_dead_variants.extend(dead_variants);
+        }
+    }
+
+    fn visit_item_union(&mut self, i: &'ast syn::ItemUnion) {
+        self.count_type_name(&i.ident);
+        for ident in i.fields.named.iter().filter_map(|f| f.ident.as_ref()) {
+            *self
+                .output_field_name_counts
+                .entry(ident.clone())
+                .or_default() += 1;
+        }
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.push_dead_item(&i.ident);
         }
     }
 
     fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
         if self.is_dead_code(Some(&i.sig.ident), &i.sig.ident.span()) {
-            self.output_dead_fn_identifiers.push(i.sig.ident.clone());
+            self.push_dead_item(&i.sig.ident);
+        } else {
+            BodyImportVisitor(self).visit_block(&i.block);
         }
     }
 
     fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
         if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
-            self.output_dead_trait_identifiers.push(i.ident.clone());
+            self.push_dead_item(&i.ident);
+        }
+    }
+
+    fn visit_item_const(&mut self, i: &'ast syn::ItemConst) {
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.push_dead_item(&i.ident);
+        }
+    }
+
+    fn visit_item_static(&mut self, i: &'ast syn::ItemStatic) {
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.push_dead_item(&i.ident);
+        }
+    }
+
+    fn visit_item_type(&mut self, i: &'ast syn::ItemType) {
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.push_dead_item(&i.ident);
+        }
+    }
+
+    // Macros live in a namespace of their own and can shadow each other, so they are told apart
+    // by their position.
+    fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+        let Some(ident) = &i.ident else {
+            return;
+        };
+        let is_dead = if has_attr(&i.attrs, "macro_export") {
+            !self.invoked_macros.contains(&ident.to_string())
+        } else {
+            self.is_dead_code(Some(ident), &ident.span())
+        };
+        if is_dead {
+            self.output_dead_macro_ranges.push(i.span().byte_range());
         }
     }
 
-    fn visit_use_path(&mut self, i: &'ast syn::UsePath) {
+    // rustc reports unused imports by the span of the whole tree, like `std::fmt` in
+    // `use std::fmt;`, or by the spans of the unused members of a group.
+    fn visit_use_tree(&mut self, i: &'ast syn::UseTree) {
         if self.is_dead_code(None, &i.span()) {
-            self.output_dead_use_identifiers.push(i.ident.clone());
+            self.output_dead_use_tree_ranges.push(i.span().byte_range());
+        }
+        syn::visit::visit_use_tree(self, i);
+    }
+
+    // Associated items are told apart by their position rather than their name, since names
+    // like `new` are shared by many impls.
+    fn visit_impl_item_fn(&mut self, i: &'ast syn::ImplItemFn) {
+        if self.is_dead_code(Some(&i.sig.ident), &i.sig.ident.span()) {
+            self.output_dead_impl_item_ranges
+                .push(i.span().byte_range());
+        } else {
+            BodyImportVisitor(self).visit_block(&i.block);
+        }
+    }
+
+    fn visit_impl_item_const(&mut self, i: &'ast syn::ImplItemConst) {
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.output_dead_impl_item_ranges
+                .push(i.span().byte_range());
+        }
+    }
+
+    fn visit_impl_item_type(&mut self, i: &'ast syn::ImplItemType) {
+        if self.is_dead_code(Some(&i.ident), &i.ident.span()) {
+            self.output_dead_impl_item_ranges
+                .push(i.span().byte_range());
         }
     }
 }
 
+/// Visits the imports in a function body. The other items there aren't visited, since they
+/// aren't in any module and couldn't be told apart from the items of the enclosing one.
+struct BodyImportVisitor<'a, V>(&'a mut V);
+
+impl<'ast, V: Visit<'ast>> Visit<'ast> for BodyImportVisitor<'_, V> {
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        self.0.visit_item_use(i);
+    }
+}
+
+/// Collects every path in a syntax tree, including the ones nested in generic arguments.
+#[derive(Default)]
+pub(crate) struct PathVisitor<'ast> {
+    pub(crate) output_paths: Vec<&'ast syn::Path>,
+}
+
+impl<'ast> Visit<'ast> for PathVisitor<'ast> {
+    fn visit_path(&mut self, i: &'ast syn::Path) {
+        self.output_paths.push(i);
+        syn::visit::visit_path(self, i);
+    }
+}
+
 struct DeadCodeVisitor {
-    dead_struct_identifiers: Vec<Ident>,
-    dead_fn_identifiers: Vec<Ident>,
-    dead_trait_identifiers: Vec<Ident>,
-    dead_use_identifiers: Vec<Ident>,
+    dead_items: BTreeSet<ItemPath>,
+    scopes: Scopes,
+    /// Module of the items being visited
+    module: ItemPath,
+    dead_use_tree_ranges: Vec<Range<usize>>,
+    dead_impl_item_ranges: Vec<Range<usize>>,
     output_dead_spans: Vec<Span>,
+    output_dead_ranges: Vec<Range<usize>>,
 }
 
 impl DeadCodeVisitor {
-    fn new(
-        dead_struct_identifiers: Vec<Ident>,
-        dead_fn_identifiers: Vec<Ident>,
-        dead_trait_identifiers: Vec<Ident>,
-        dead_use_identifiers: Vec<Ident>,
-    ) -> Self {
+    fn new(visitor: DeadIdentifierVisitor, scopes: Scopes) -> Self {
         Self {
-            dead_struct_identifiers,
-            dead_fn_identifiers,
-            dead_trait_identifiers,
-            dead_use_identifiers,
+            dead_items: visitor.output_dead_items,
+            scopes,
+            module: Vec::new(),
+            dead_use_tree_ranges: visitor.output_dead_use_tree_ranges,
+            dead_impl_item_ranges: visitor.output_dead_impl_item_ranges,
             output_dead_spans: Vec::new(),
+            output_dead_ranges: visitor.output_dead_macro_ranges,
         }
     }
+
+    /// Whether the item named `ident` in the current module is dead.
+    fn is_dead_item(&self, ident: &Ident) -> bool {
+        let mut path = self.module.clone();
+        path.push(ident.to_string());
+        self.dead_items.contains(&path)
+    }
 }
 
 impl<'ast> Visit<'ast> for DeadCodeVisitor {
     fn visit_item_struct(&mut self, i: &'ast syn::ItemStruct) {
-        if self.dead_struct_identifiers.contains(&i.ident) {
+        if self.is_dead_item(&i.ident) {
+            self.output_dead_spans.push(i.span());
+        }
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast syn::ItemEnum) {
+        if self.is_dead_item(&i.ident) {
+            self.output_dead_spans.push(i.span());
+        }
+    }
+
+    fn visit_item_union(&mut self, i: &'ast syn::ItemUnion) {
+        if self.is_dead_item(&i.ident) {
             self.output_dead_spans.push(i.span());
         }
     }
 
     fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
-        if self.dead_fn_identifiers.contains(&i.sig.ident) {
+        if self.is_dead_item(&i.sig.ident) {
             self.output_dead_spans.push(i.span());
+        } else {
+            BodyImportVisitor(self).visit_block(&i.block);
         }
     }
 
     fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
-        if self.dead_trait_identifiers.contains(&i.ident) {
+        if self.is_dead_item(&i.ident) {
+            self.output_dead_spans.push(i.span());
+        }
+    }
+
+    fn visit_item_const(&mut self, i: &'ast syn::ItemConst) {
+        if self.is_dead_item(&i.ident) {
             self.output_dead_spans.push(i.span());
         }
     }
 
+    fn visit_item_static(&mut self, i: &'ast syn::ItemStatic) {
+        if self.is_dead_item(&i.ident) {
+            self.output_dead_spans.push(i.span());
+        }
+    }
+
+    fn visit_item_type(&mut self, i: &'ast syn::ItemType) {
+        if self.is_dead_item(&i.ident) {
+            self.output_dead_spans.push(i.span());
+        }
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+
     fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
-        if let syn::Type::Path(type_path) = i.self_ty.as_ref() {
-            if type_path.path.segments.len() != 1 {
-                unimplemented!()
+        // An impl goes away with any of the types and traits in its header, like `Bound`,
+        // `Trait` and `Wrapper` in `impl<T: Bound> Trait for Vec<Wrapper<T>>`.
+        let mut header_paths = PathVisitor::default();
+        header_paths.visit_generics(&i.generics);
+        if let Some((_, trait_path, _)) = &i.trait_ {
+            header_paths.visit_path(trait_path);
+        }
+        header_paths.visit_type(&i.self_ty);
+        let type_params: BTreeSet<&Ident> = i.generics.type_params().map(|p| &p.ident).collect();
+        let mentions_dead_item = header_paths
+            .output_paths
+            .iter()
+            .filter(|path| {
+                path.segments
+                    .first()
+                    .is_some_and(|s| !type_params.contains(&s.ident))
+            })
+            .filter_map(|path| self.scopes.resolve(&self.module, path))
+            .any(|path| self.dead_items.contains(&path));
+        if mentions_dead_item {
+            self.output_dead_spans.push(i.span());
+            return;
+        }
+        for item in &i.items {
+            if let syn::ImplItem::Fn(item_fn) = item {
+                let range = item_fn.span().byte_range();
+                if !self.dead_impl_item_ranges.contains(&range) {
+                    BodyImportVisitor(self).visit_block(&item_fn.block);
+                }
             }
-            if self
-                .dead_struct_identifiers
-                .contains(&type_path.path.segments.last().unwrap().ident)
-            {
-                // TODO: Assuming that struct is in same module or that there's no name collisions
-                self.output_dead_spans.push(i.span());
+        }
+        if i.trait_.is_some() {
+            return;
+        }
+
+        let dead_items: Vec<Span> = i
+            .items
+            .iter()
+            .map(|item| item.span())
+            .filter(|span| self.dead_impl_item_ranges.contains(&span.byte_range()))
+            .collect();
+        if dead_items.len() == i.items.len() {
+            // Nothing is left in the inherent impl
+            self.output_dead_spans.push(i.span());
+        } else {
+            self.output_dead_spans.extend(dead_items);
+        }
+    }
+
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        let mut dead_ranges = Vec::new();
+        let is_unused = |tree: &syn::UseTree, _: &[String]| {
+            self.dead_use_tree_ranges
+                .contains(&tree.span().byte_range())
+        };
+        if is_dead_use_tree(&i.tree, &mut Vec::new(), &is_unused, &mut dead_ranges) {
+            self.output_dead_ranges.push(i.span().byte_range());
+        } else {
+            self.output_dead_ranges.extend(dead_ranges);
+        }
+    }
+}
+
+/// Whether all of `tree` is dead according to `is_dead`, which is given every subtree along
+/// with the path leading to it. Otherwise collects the ranges of the dead parts, like `Write, `
+/// in `std::io::{Write, stdin}`.
+pub(crate) fn is_dead_use_tree(
+    tree: &syn::UseTree,
+    prefix: &mut Vec<String>,
+    is_dead: &dyn Fn(&syn::UseTree, &[String]) -> bool,
+    dead_ranges: &mut Vec<Range<usize>>,
+) -> bool {
+    if is_dead(tree, prefix) {
+        return true;
+    }
+    match tree {
+        syn::UseTree::Path(use_path) => {
+            prefix.push(use_path.ident.to_string());
+            let mut tree_dead_ranges = Vec::new();
+            let is_dead = is_dead_use_tree(&use_path.tree, prefix, is_dead, &mut tree_dead_ranges);
+            prefix.pop();
+            if is_only_self_left(&use_path.tree, &tree_dead_ranges) {
+                // `vector::{self}` is just `vector`
+                let start = use_path.colon2_token.span().byte_range().start;
+                dead_ranges.push(start..use_path.tree.span().byte_range().end);
+            } else {
+                dead_ranges.extend(tree_dead_ranges);
             }
+            is_dead
         }
-        if let Some(trait_) = i.trait_.as_ref() {
-            let type_path = &trait_.1;
-            if type_path.segments.len() != 1 {
-                unimplemented!()
+        syn::UseTree::Group(use_group) => {
+            let mut dead_items = Vec::new();
+            for pair in use_group.items.pairs() {
+                let mut item_dead_ranges = Vec::new();
+                if is_dead_use_tree(pair.value(), prefix, is_dead, &mut item_dead_ranges) {
+                    dead_items.push(pair_range(&pair));
+                } else {
+                    dead_ranges.extend(item_dead_ranges);
+                }
             }
-            if self
-                .dead_trait_identifiers
-                .contains(&type_path.segments.last().unwrap().ident)
-            {
-                self.output_dead_spans.push(i.span());
+            if dead_items.len() == use_group.items.len() {
+                return true;
+            }
+            dead_ranges.extend(dead_items);
+            false
+        }
+        syn::UseTree::Name(_) | syn::UseTree::Rename(_) | syn::UseTree::Glob(_) => false,
+    }
+}
+
+/// Whether `tree` is a group where only `self` is left once `dead_ranges` are removed.
+fn is_only_self_left(tree: &syn::UseTree, dead_ranges: &[Range<usize>]) -> bool {
+    let syn::UseTree::Group(use_group) = tree else {
+        return false;
+    };
+    let mut left = use_group
+        .items
+        .pairs()
+        .filter(|pair| !dead_ranges.contains(&pair_range(pair)))
+        .map(|pair| pair.into_value());
+    matches!(
+        (left.next(), left.next()),
+        (Some(syn::UseTree::Name(use_name)), None) if use_name.ident == "self"
+    )
+}
+
+/// Finds what is left behind once the items of a module are removed: modules without items,
+/// and imports of items that no longer exist.
+struct DanglingItemVisitor<'a> {
+    /// Scopes before the last removal
+    old_scopes: &'a Scopes,
+    scopes: &'a Scopes,
+    /// How many times each identifier appears in the file
+    ident_counts: &'a BTreeMap<String, usize>,
+    /// Local traits with the names of their items
+    traits: &'a BTreeMap<ItemPath, BTreeSet<String>>,
+    /// Names mentioned by the paths, method calls and macro invocations of the file
+    mentioned_names: &'a BTreeSet<String>,
+    /// Also remove the imports of local traits nothing names, whose items nothing mentions
+    guess_unused_traits: bool,
+    module: ItemPath,
+    output_dead_ranges: Vec<Range<usize>>,
+}
+
+impl DanglingItemVisitor<'_> {
+    /// Whether a use path resolved to an item of the crate before the last removal, but
+    /// doesn't anymore. Paths into other crates never resolve, so they are left alone.
+    fn is_dangling(&self, tree: &syn::UseTree, prefix: &[String]) -> bool {
+        let mut path = prefix.to_vec();
+        match tree {
+            syn::UseTree::Name(syn::UseName { ident, .. })
+            | syn::UseTree::Rename(syn::UseRename { ident, .. }) => {
+                if ident != "self" {
+                    path.push(ident.to_string());
+                }
             }
+            syn::UseTree::Glob(_) => (),
+            syn::UseTree::Path(_) | syn::UseTree::Group(_) => return false,
         }
+        self.scopes.resolve_segments(&self.module, &path).is_none()
+            && self
+                .old_scopes
+                .resolve_segments(&self.module, &path)
+                .is_some()
+    }
+
+    /// Whether an import is never named anywhere else in the file, which rustc would report
+    /// in its next run. Traits are used without being named, so imports that might be traits
+    /// are left to rustc: anything with an uppercase name that isn't a local item. When
+    /// guessing, local traits go too if none of their items are mentioned, since removing a
+    /// trait still in use could make a call resolve to another method with the same name.
+    fn is_unreferenced(&self, tree: &syn::UseTree, prefix: &[String]) -> bool {
+        let (ident, name) = match tree {
+            syn::UseTree::Name(syn::UseName { ident }) => (ident, ident),
+            syn::UseTree::Rename(syn::UseRename { ident, rename, .. }) if rename != "_" => {
+                (ident, rename)
+            }
+            _ => return false,
+        };
+        let name = name.to_string();
+        if ident == "self" || self.ident_counts.get(&name) != Some(&1) {
+            return false;
+        }
+        if name.starts_with(|c: char| c.is_lowercase()) {
+            return true;
+        }
+        let mut path = prefix.to_vec();
+        path.push(ident.to_string());
+        let Some(path) = self.scopes.resolve_segments(&self.module, &path) else {
+            return false;
+        };
+        if !self.scopes.is_trait(&path) {
+            return true;
+        }
+        self.guess_unused_traits
+            && self
+                .traits
+                .get(&path)
+                .is_some_and(|items| items.is_disjoint(self.mentioned_names))
+    }
+}
+
+impl<'ast> Visit<'ast> for DanglingItemVisitor<'_> {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        if i.content
+            .as_ref()
+            .is_some_and(|(_, items)| items.is_empty())
+        {
+            self.output_dead_ranges.push(i.span().byte_range());
+            return;
+        }
+        self.module.push(i.ident.to_string());
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
     }
 
     fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
-        match &i.tree {
-            syn::UseTree::Path(use_path) => if self.dead_use_identifiers.contains(&use_path.ident) {
-                self.output_dead_spans.push(i.span());
-            } ,
-            syn::UseTree::Name(_use_name) => todo!(),
-            syn::UseTree::Rename(_use_rename) => todo!(),
-            syn::UseTree::Glob(_use_glob) => todo!(),
-            syn::UseTree::Group(_use_group) => todo!(),
+        if i.leading_colon.is_some() {
+            return;
+        }
+        let mut dead_ranges = Vec::new();
+        let is_dangling = |tree: &syn::UseTree, prefix: &[String]| {
+            self.is_dangling(tree, prefix) || self.is_unreferenced(tree, prefix)
+        };
+        if is_dead_use_tree(&i.tree, &mut Vec::new(), &is_dangling, &mut dead_ranges) {
+            self.output_dead_ranges.push(i.span().byte_range());
+        } else {
+            self.output_dead_ranges.extend(dead_ranges);
         }
     }
 }
 
-fn is_test_module(item_mod: &syn::ItemMod) -> bool {
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+fn derives(attrs: &[syn::Attribute], trait_name: &str) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .any(|path| path.segments.last().is_some_and(|s| s.ident == trait_name))
+}
+
+fn collect_idents(tokens: TokenStream, idents: &mut BTreeSet<Ident>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident);
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            TokenTree::Punct(_) | TokenTree::Literal(_) => (),
+        }
+    }
+}
+
+/// Whether every generic parameter is still used by the types that are left, as unused
+/// parameters are an error.
+fn uses_all_generics(generics: &syn::Generics, types: impl Iterator<Item = TokenStream>) -> bool {
+    let mut idents = BTreeSet::new();
+    for ty in types {
+        collect_idents(ty, &mut idents);
+    }
+    generics.params.iter().all(|param| match param {
+        syn::GenericParam::Lifetime(param) => idents.contains(&param.lifetime.ident),
+        syn::GenericParam::Type(param) => idents.contains(&param.ident),
+        syn::GenericParam::Const(param) => idents.contains(&param.ident),
+    })
+}
+
+/// Range of an element of a comma separated list together with its trailing comma.
+fn pair_range<T: Spanned, P: Spanned>(pair: &Pair<&T, &P>) -> Range<usize> {
+    let start = pair.value().span().byte_range().start;
+    let end = match pair.punct() {
+        Some(punct) => punct.span().byte_range().end,
+        None => pair.value().span().byte_range().end,
+    };
+    start..end
+}
+
+/// Whether `expr` can be dropped without changing what the program does. Calls are assumed to
+/// be free of side effects only for constructors and the usual `new` and `default` functions.
+fn is_pure(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Lit(_) | syn::Expr::Path(_) => true,
+        syn::Expr::Paren(e) => is_pure(&e.expr),
+        syn::Expr::Group(e) => is_pure(&e.expr),
+        syn::Expr::Reference(e) => is_pure(&e.expr),
+        syn::Expr::Field(e) => is_pure(&e.base),
+        syn::Expr::Cast(e) => is_pure(&e.expr),
+        syn::Expr::Unary(e) => !matches!(e.op, syn::UnOp::Deref(_)) && is_pure(&e.expr),
+        syn::Expr::Binary(e) => {
+            matches!(
+                e.op,
+                syn::BinOp::Add(_)
+                    | syn::BinOp::Sub(_)
+                    | syn::BinOp::Mul(_)
+                    | syn::BinOp::And(_)
+                    | syn::BinOp::Or(_)
+                    | syn::BinOp::BitXor(_)
+                    | syn::BinOp::BitAnd(_)
+                    | syn::BinOp::BitOr(_)
+                    | syn::BinOp::Eq(_)
+                    | syn::BinOp::Lt(_)
+                    | syn::BinOp::Le(_)
+                    | syn::BinOp::Ne(_)
+                    | syn::BinOp::Ge(_)
+                    | syn::BinOp::Gt(_)
+            ) && is_pure(&e.left)
+                && is_pure(&e.right)
+        }
+        syn::Expr::Tuple(e) => e.elems.iter().all(is_pure),
+        syn::Expr::Array(e) => e.elems.iter().all(is_pure),
+        syn::Expr::Repeat(e) => is_pure(&e.expr) && is_pure(&e.len),
+        syn::Expr::Call(e) => {
+            let syn::Expr::Path(func) = e.func.as_ref() else {
+                return false;
+            };
+            let Some(name) = func.path.segments.last().map(|s| s.ident.to_string()) else {
+                return false;
+            };
+            (name.starts_with(char::is_uppercase)
+                || ["new", "default", "with_capacity"].contains(&name.as_str()))
+                && e.args.iter().all(is_pure)
+        }
+        syn::Expr::MethodCall(e) => {
+            e.method == "clone" && e.args.is_empty() && is_pure(&e.receiver)
+        }
+        syn::Expr::Macro(e) if e.mac.path.is_ident("vec") => {
+            let tokens = &e.mac.tokens;
+            syn::parse2::<syn::Expr>(quote!([#tokens])).is_ok_and(|e| is_pure(&e))
+        }
+        _ => false,
+    }
+}
+
+/// A named struct field that is never read or an enum variant that is never constructed.
+struct DeadMember {
+    /// Struct or enum the member belongs to
+    parent: Ident,
+    ident: Ident,
+    /// Ranges to remove together with the member: its declaration, initializers and patterns
+    ranges: Vec<Range<usize>>,
+    is_removable: bool,
+}
+
+impl DeadMember {
+    fn new<T: Spanned, P: Spanned>(
+        parent: &Ident,
+        ident: &Ident,
+        declaration: &Pair<&T, &P>,
+    ) -> Self {
+        Self {
+            parent: parent.clone(),
+            ident: ident.clone(),
+            ranges: vec![pair_range(declaration)],
+            is_removable: true,
+        }
+    }
+}
+
+fn pat_path(pat: &syn::Pat) -> Option<&syn::Path> {
+    match pat {
+        syn::Pat::Path(pat) => Some(&pat.path),
+        syn::Pat::TupleStruct(pat) => Some(&pat.path),
+        syn::Pat::Struct(pat) => Some(&pat.path),
+        _ => None,
+    }
+}
+
+/// Finds everything that has to go together with the dead fields and variants, and gives up
+/// on the ones used in ways that can't be removed safely. Types are matched by name, so fields
+/// are only removed when no other type has a field with the same name, and members of types
+/// sharing their name with another type are left alone.
+struct DeadMemberVisitor {
+    dead_fields: Vec<DeadMember>,
+    dead_variants: Vec<DeadMember>,
+    /// Self types of the impls the visitor is in
+    self_types: Vec<Option<Ident>>,
+    /// Match arms along with the variants they match, removed once all of them are
+    arms: Vec<(Range<usize>, Vec<usize>)>,
+}
+
+impl DeadMemberVisitor {
+    fn new(visitor: &mut DeadIdentifierVisitor) -> Self {
+        let field_name_counts = &visitor.output_field_name_counts;
+        let type_name_counts = &visitor.output_type_name_counts;
+        Self {
+            dead_fields: std::mem::take(&mut visitor.output_dead_fields)
+                .into_iter()
+                .filter(|f| type_name_counts[&f.parent] == 1 && field_name_counts[&f.ident] == 1)
+                .collect(),
+            dead_variants: std::mem::take(&mut visitor.output_dead_variants)
+                .into_iter()
+                .filter(|v| type_name_counts[&v.parent] == 1)
+                .collect(),
+            self_types: Vec::new(),
+            arms: Vec::new(),
+        }
+    }
+
+    /// Name of the struct a struct expression or pattern refers to.
+    fn resolve_struct(&self, path: &syn::Path) -> Option<Ident> {
+        let ident = &path.segments.last()?.ident;
+        if ident == "Self" {
+            self.self_types.last().cloned().flatten()
+        } else {
+            Some(ident.clone())
+        }
+    }
+
+    fn dead_field(&self, struct_ident: Option<&Ident>, member: &syn::Member) -> Option<usize> {
+        let syn::Member::Named(ident) = member else {
+            return None;
+        };
+        self.dead_fields
+            .iter()
+            .position(|f| &f.ident == ident && struct_ident.is_none_or(|s| &f.parent == s))
+    }
+
+    /// The dead variant a path like `Enum::Variant` or `Self::Variant` refers to.
+    fn dead_variant(&self, path: &syn::Path) -> Option<usize> {
+        let [.., enum_segment, variant_segment] = path.segments.iter().collect::<Vec<_>>()[..]
+        else {
+            return None;
+        };
+        let enum_ident = if enum_segment.ident == "Self" {
+            self.self_types.last()?.as_ref()?
+        } else {
+            &enum_segment.ident
+        };
+        self.dead_variants
+            .iter()
+            .position(|v| &v.parent == enum_ident && v.ident == variant_segment.ident)
+    }
+
+    /// Gives up on the variants a path might refer to.
+    fn visit_variant_path(&mut self, path: &syn::Path) {
+        if let Some(index) = self.dead_variant(path) {
+            self.dead_variants[index].is_removable = false;
+        } else if let Some(ident) = path.get_ident() {
+            // The variant might have been imported with `use`
+            for variant in self.dead_variants.iter_mut().filter(|v| &v.ident == ident) {
+                variant.is_removable = false;
+            }
+        }
+    }
+
+    fn visit_field_pats(&mut self, i: &syn::PatStruct) {
+        let struct_ident = self.resolve_struct(&i.path);
+        for pair in i.fields.pairs() {
+            let field_pat = pair.value();
+            if let Some(index) = self.dead_field(struct_ident.as_ref(), &field_pat.member) {
+                self.dead_fields[index].ranges.push(pair_range(&pair));
+            }
+            self.visit_pat(&field_pat.pat);
+        }
+    }
+
+    fn output_ranges(self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .dead_fields
+            .iter()
+            .chain(&self.dead_variants)
+            .filter(|m| m.is_removable)
+            .flat_map(|m| m.ranges.iter().cloned())
+            .collect();
+        for (range, variants) in self.arms {
+            if variants.iter().all(|&v| self.dead_variants[v].is_removable) {
+                ranges.push(range);
+            }
+        }
+        ranges
+    }
+}
+
+impl<'ast> Visit<'ast> for DeadMemberVisitor {
+    fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
+        let self_type = match i.self_ty.as_ref() {
+            syn::Type::Path(type_path) => type_path.path.segments.last().map(|s| s.ident.clone()),
+            _ => None,
+        };
+        self.self_types.push(self_type);
+        syn::visit::visit_item_impl(self, i);
+        self.self_types.pop();
+    }
+
+    fn visit_expr_struct(&mut self, i: &'ast syn::ExprStruct) {
+        self.visit_variant_path(&i.path);
+        let struct_ident = self.resolve_struct(&i.path);
+        for pair in i.fields.pairs() {
+            let field_value = pair.value();
+            if let Some(index) = self.dead_field(struct_ident.as_ref(), &field_value.member) {
+                if is_pure(&field_value.expr) {
+                    self.dead_fields[index].ranges.push(pair_range(&pair));
+                } else {
+                    self.dead_fields[index].is_removable = false;
+                }
+            }
+            self.visit_expr(&field_value.expr);
+        }
+        if let Some(rest) = &i.rest {
+            self.visit_expr(rest);
+        }
+    }
+
+    fn visit_pat_struct(&mut self, i: &'ast syn::PatStruct) {
+        self.visit_variant_path(&i.path);
+        self.visit_field_pats(i);
+    }
+
+    fn visit_expr_field(&mut self, i: &'ast syn::ExprField) {
+        if let Some(index) = self.dead_field(None, &i.member) {
+            self.dead_fields[index].is_removable = false;
+        }
+        syn::visit::visit_expr_field(self, i);
+    }
+
+    fn visit_arm(&mut self, i: &'ast syn::Arm) {
+        let cases: Vec<&syn::Pat> = match &i.pat {
+            syn::Pat::Or(pat_or) => pat_or.cases.iter().collect(),
+            pat => vec![pat],
+        };
+        let dead_variants: Vec<Option<usize>> = cases
+            .iter()
+            .map(|case| pat_path(case).and_then(|path| self.dead_variant(path)))
+            .collect();
+        if dead_variants.iter().all(Option::is_some) {
+            self.arms.push((
+                i.span().byte_range(),
+                dead_variants.iter().flatten().copied().collect(),
+            ));
+        }
+        for (index, case) in cases.iter().enumerate() {
+            match (dead_variants[index], &i.pat) {
+                (Some(variant), syn::Pat::Or(pat_or)) if cases.len() > 1 => {
+                    // Remove the case along with a `|` next to it
+                    let mut range = case.span().byte_range();
+                    match pat_or.cases.pairs().nth(index).unwrap().punct() {
+                        Some(punct) => range.end = punct.span().byte_range().end,
+                        None => {
+                            let previous = pat_or.cases.pairs().nth(index - 1).unwrap();
+                            range.start = previous.punct().unwrap().span().byte_range().start;
+                        }
+                    }
+                    self.dead_variants[variant].ranges.push(range);
+                }
+                (Some(_), _) => (),
+                (None, _) => {
+                    self.visit_pat(case);
+                    continue;
+                }
+            }
+            // The path of a removed case is not a use of the variant, but what it binds might
+            // still have to be edited if the variant stays after all.
+            match case {
+                syn::Pat::TupleStruct(pat) => {
+                    for elem in &pat.elems {
+                        self.visit_pat(elem);
+                    }
+                }
+                syn::Pat::Struct(pat) => self.visit_field_pats(pat),
+                _ => (),
+            }
+        }
+        if let Some((_, guard)) = &i.guard {
+            self.visit_expr(guard);
+        }
+        self.visit_expr(&i.body);
+    }
+
+    fn visit_expr_path(&mut self, i: &'ast syn::ExprPath) {
+        self.visit_variant_path(&i.path);
+        syn::visit::visit_expr_path(self, i);
+    }
+
+    fn visit_pat_tuple_struct(&mut self, i: &'ast syn::PatTupleStruct) {
+        self.visit_variant_path(&i.path);
+        syn::visit::visit_pat_tuple_struct(self, i);
+    }
+
+    fn visit_pat_ident(&mut self, i: &'ast syn::PatIdent) {
+        for variant in self.dead_variants.iter_mut().filter(|v| v.ident == i.ident) {
+            variant.is_removable = false;
+        }
+        syn::visit::visit_pat_ident(self, i);
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        // Macro arguments can't be edited reliably
+        let mut idents = BTreeSet::new();
+        collect_idents(i.tokens.clone(), &mut idents);
+        for member in self.dead_fields.iter_mut().chain(&mut self.dead_variants) {
+            if idents.contains(&member.ident) {
+                member.is_removable = false;
+            }
+        }
+    }
+}
+
+pub(crate) fn is_test_module(item_mod: &syn::ItemMod) -> bool {
     item_mod
         .attrs
         .iter()
@@ -261,10 +1123,86 @@ pub fn remove_tests(src: String) -> anyhow::Result<String> {
     Ok(src)
 }
 
+/// Collects the ranges of the tokens to keep, skipping cosmetic attributes like docs, which
+/// are tokens too. Macro bodies are covered as well.
+fn collect_kept_token_ranges(tokens: TokenStream, kept: &mut Vec<Range<usize>>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_pound = matches!(&tokens[i], TokenTree::Punct(p) if p.as_char() == '#');
+        let bang = matches!(tokens.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '!');
+        let attribute_index = if bang { i + 2 } else { i + 1 };
+        if let (true, Some(TokenTree::Group(group))) = (is_pound, tokens.get(attribute_index)) {
+            let path: Vec<String> = group
+                .stream()
+                .into_iter()
+                .map_while(|t| match t {
+                    TokenTree::Ident(ident) => Some(Some(ident.to_string())),
+                    TokenTree::Punct(p) if p.as_char() == ':' => Some(None),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            if group.delimiter() == proc_macro2::Delimiter::Bracket
+                && is_cosmetic_attribute_path(&path)
+            {
+                i = attribute_index + 1;
+                continue;
+            }
+        }
+        match &tokens[i] {
+            TokenTree::Group(group) => {
+                kept.push(group.span_open().byte_range());
+                collect_kept_token_ranges(group.stream(), kept);
+                kept.push(group.span_close().byte_range());
+            }
+            token => kept.push(token.span().byte_range()),
+        }
+        i += 1;
+    }
+}
+
+/// Removes docs, comments and cosmetic attributes like `#[must_use]` from the inlined
+/// library modules named in `libs`, leaving the code of the binary as it is.
+pub fn remove_lib_docs(src: String, libs: &BTreeSet<String>) -> anyhow::Result<String> {
+    let ast = syn::parse_file(&src)?;
+    let lib_ranges: Vec<Range<usize>> = ast
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Mod(item_mod) if libs.contains(&item_mod.ident.to_string()) => {
+                Some(item_mod.span().byte_range())
+            }
+            _ => None,
+        })
+        .collect();
+    // Lexed straight from the source, so every token has the span of its text, unlike the
+    // tokens syn prints back from the syntax tree.
+    let mut kept = Vec::new();
+    collect_kept_token_ranges(syn::parse_str(&src)?, &mut kept);
+    kept.sort_by_key(|range| range.start);
+    let mut edits = Vec::new();
+    // Everything between two tokens is whitespace or comments.
+    for pair in kept.windows(2) {
+        let gap = pair[0].end..pair[1].start;
+        let is_in_lib = lib_ranges
+            .iter()
+            .any(|lib| lib.start <= gap.start && gap.end <= lib.end);
+        if is_in_lib && !src[gap.clone()].trim().is_empty() {
+            edits.push((gap, "\n".to_string()));
+        }
+    }
+    Ok(replace_ranges(&src, edits))
+}
+
 fn remove_spans(src: String, spans: Vec<Span>) -> anyhow::Result<String> {
+    remove_ranges(src, spans.iter().map(Span::byte_range).collect())
+}
+
+fn remove_ranges(src: String, ranges: Vec<Range<usize>>) -> anyhow::Result<String> {
     let mut dead_bytes = vec![false; src.len()];
-    for span in spans {
-        for i in span.byte_range() {
+    for range in ranges {
+        for i in range {
             dead_bytes[i] = true;
         }
     }
@@ -280,47 +1218,178 @@ fn remove_spans(src: String, spans: Vec<Span>) -> anyhow::Result<String> {
     Ok(src)
 }
 
-fn remove_dead_code_inner(src: String) -> anyhow::Result<String> {
-    let dead_code_diagnostics: Vec<Diagnostic> = rustc_diagnostics(&src)?
+fn remove_dead_code_inner(
+    src: String,
+    remove_dead_fields: bool,
+    guess_unused_traits: bool,
+    diagnostics: Vec<Diagnostic>,
+) -> anyhow::Result<String> {
+    let dead_code_diagnostics: Vec<Diagnostic> = diagnostics
         .into_iter()
-        .filter(|d| d.code.as_ref().map_or(false, |c| c.code == "dead_code" || c.code == "unused_imports"))
+        .filter(|d| {
+            d.code.as_ref().is_some_and(|c| {
+                matches!(
+                    c.code.as_str(),
+                    "dead_code" | "unused_imports" | "unused_macros"
+                )
+            })
+        })
         .collect();
 
+    // Grouped diagnostics like "multiple methods are never used" have a primary span for each
+    // item, next to secondary spans labelling the surrounding impl, struct or enum.
     let dead_code_diagnostic_spans: Vec<DiagnosticSpan> = dead_code_diagnostics
         .into_iter()
-        .map(|d| d.spans.into_iter().next().unwrap())
+        .flat_map(|d| d.spans.into_iter().filter(|s| s.is_primary))
         .collect();
 
     let ast = syn::parse_file(&src)?;
-    let mut visitor = DeadIdentifierVisitor::new(dead_code_diagnostic_spans);
+    let mut visitor =
+        DeadIdentifierVisitor::new(dead_code_diagnostic_spans, invoked_macro_names(&ast));
     visitor.visit_file(&ast);
 
-    let mut visitor = DeadCodeVisitor::new(
-        visitor.output_dead_struct_identifiers,
-        visitor.output_dead_fn_identifiers,
-        visitor.output_dead_trait_identifiers,
-        visitor.output_dead_use_identifiers,
-    );
+    let mut dead_ranges = Vec::new();
+    if remove_dead_fields {
+        let mut member_visitor = DeadMemberVisitor::new(&mut visitor);
+        member_visitor.visit_file(&ast);
+        dead_ranges.extend(member_visitor.output_ranges());
+    }
+
+    let mut visitor = DeadCodeVisitor::new(visitor, Scopes::new(&ast));
     visitor.visit_file(&ast);
+    dead_ranges.extend(visitor.output_dead_ranges);
+    dead_ranges.extend(reach::unreachable_trait_ranges(&ast));
 
-    let src= remove_spans(src, visitor.output_dead_spans)?;
+    let mut dead_ranges = with_line_breaks(&src, dead_ranges);
+    dead_ranges.extend(visitor.output_dead_spans.iter().map(Span::byte_range));
 
-    // TODO: Remove empty modules
+    let new_src = remove_ranges(src.clone(), dead_ranges)?;
+    remove_dangling_items(&src, new_src, guess_unused_traits)
+}
 
-    Ok(src)
+/// Extends ranges over the rest of their last line, or rustfmt leaves an empty line behind.
+fn with_line_breaks(src: &str, ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges
+        .into_iter()
+        .map(|range| {
+            let rest = &src[range.end..];
+            let line_rest = rest.trim_start_matches([' ', '\t']);
+            let line_rest = line_rest.strip_prefix('\n').unwrap_or(line_rest);
+            range.start..range.end + rest.len() - line_rest.len()
+        })
+        .collect()
+}
+
+fn count_idents(tokens: TokenStream, counts: &mut BTreeMap<String, usize>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => *counts.entry(ident.to_string()).or_default() += 1,
+            TokenTree::Group(group) => count_idents(group.stream(), counts),
+            TokenTree::Punct(_) | TokenTree::Literal(_) => (),
+        }
+    }
 }
 
-pub fn remove_dead_code(mut src: String) -> anyhow::Result<String> {
+/// Removes empty modules and the imports of items removed from `old_src`, until there are
+/// none left. Those imports would not compile, so rustc can't report anything else as dead
+/// until they are gone. Imports nothing names anymore go too, saving a run of rustc.
+fn remove_dangling_items(
+    old_src: &str,
+    mut src: String,
+    guess_unused_traits: bool,
+) -> anyhow::Result<String> {
+    let mut old_scopes = Scopes::new(&syn::parse_file(old_src)?);
     loop {
-        let new_src = remove_dead_code_inner(src.clone())?;
-        if src == new_src {
+        let ast = syn::parse_file(&src)?;
+        let scopes = Scopes::new(&ast);
+        let mut ident_counts = BTreeMap::new();
+        count_idents(ast.to_token_stream(), &mut ident_counts);
+        let traits = reach::local_traits(&ast);
+        let mentioned_names = reach::mentioned_names(&ast);
+        let mut visitor = DanglingItemVisitor {
+            old_scopes: &old_scopes,
+            scopes: &scopes,
+            ident_counts: &ident_counts,
+            traits: &traits,
+            mentioned_names: &mentioned_names,
+            guess_unused_traits,
+            module: Vec::new(),
+            output_dead_ranges: Vec::new(),
+        };
+        visitor.visit_file(&ast);
+        if visitor.output_dead_ranges.is_empty() {
             return Ok(src);
-        } else {
-            src = new_src;
         }
+        let dead_ranges = with_line_breaks(&src, visitor.output_dead_ranges);
+        src = remove_ranges(src, dead_ranges)?;
+        old_scopes = scopes;
     }
 }
 
+/// What [`remove_dead_code_with_stats`] went through.
+#[derive(Debug, Clone, Default)]
+pub struct DeadCodeStats {
+    /// Times rustc was run, including the last run that found nothing left to remove
+    pub rustc_runs: usize,
+    /// Time spent waiting for rustc
+    pub rustc_time: Duration,
+    pub total_time: Duration,
+}
+
+/// Removes the code rustc reports as dead until there is none left. With `remove_dead_fields`,
+/// struct fields that are never read and enum variants that are never constructed are removed
+/// too, wherever that can be done safely.
+pub fn remove_dead_code(src: String, remove_dead_fields: bool) -> anyhow::Result<String> {
+    Ok(remove_dead_code_with_stats(src, remove_dead_fields, DEFAULT_EDITION)?.0)
+}
+
+/// Like [`remove_dead_code`], also reporting how many times rustc had to run and for how long.
+/// Each run reports everything that is dead at once, and is only repeated for what becomes
+/// dead once that is gone, like imports the removed code used.
+pub fn remove_dead_code_with_stats(
+    mut src: String,
+    remove_dead_fields: bool,
+    edition: &str,
+) -> anyhow::Result<(String, DeadCodeStats)> {
+    let start = Instant::now();
+    let mut stats = DeadCodeStats::default();
+    // Imports of traits only used through their methods are never named, so removing every
+    // import nothing names is a guess. The next run of rustc, which is needed anyway to tell
+    // whether anything else became dead, also tells whether the guess broke the build. If it
+    // did, the diagnostics of the run before the guess still apply to the source before it.
+    let mut guess_unused_traits = true;
+    let mut before_guess: Option<(String, Vec<Diagnostic>)> = None;
+    loop {
+        let rustc_start = Instant::now();
+        let mut diagnostics = rustc_diagnostics(&src, edition)?;
+        stats.rustc_runs += 1;
+        stats.rustc_time += rustc_start.elapsed();
+        if let Some((src_before_guess, diagnostics_before_guess)) = before_guess.take() {
+            if diagnostics
+                .iter()
+                .any(|d| d.level == DiagnosticLevel::Error)
+            {
+                src = src_before_guess;
+                diagnostics = diagnostics_before_guess;
+                guess_unused_traits = false;
+            }
+        }
+        if guess_unused_traits {
+            before_guess = Some((src.clone(), diagnostics.clone()));
+        }
+        let new_src = remove_dead_code_inner(
+            src.clone(),
+            remove_dead_fields,
+            guess_unused_traits,
+            diagnostics,
+        )?;
+        if src == new_src {
+            stats.total_time = start.elapsed();
+            return Ok((src, stats));
+        }
+        src = new_src;
+    }
+}
 
 #[derive(Default)]
 /// 🍻
@@ -330,9 +1399,8 @@ struct PubVisitor {
 
 impl<'ast> Visit<'ast> for PubVisitor {
     fn visit_visibility(&mut self, i: &'ast syn::Visibility) {
-        match i {
-            syn::Visibility::Public(token) => self.output_pub_token_spans.push(token.span()),
-            _ => (),
+        if let syn::Visibility::Public(token) = i {
+            self.output_pub_token_spans.push(token.span());
         }
     }
 }
@@ -343,14 +1411,12 @@ pub fn replace_pub_with_pub_crate(src: String) -> anyhow::Result<String> {
     pub_visitor.visit_file(&ast);
     let output_pub_token_spans = pub_visitor.output_pub_token_spans;
     let span_start_bytes: BTreeSet<usize> = output_pub_token_spans.iter().map(|span| span.byte_range().start).collect();
-    let span_bytes: BTreeSet<usize> = output_pub_token_spans.iter().map(|span| span.byte_range().into_iter()).flatten().collect();
+    let span_bytes: BTreeSet<usize> = output_pub_token_spans.iter().flat_map(|span| span.byte_range()).collect();
     let mut new_src: Vec<u8> = Vec::new();
     for (i, b) in src.bytes().enumerate() {
         if span_start_bytes.contains(&i) {
             new_src.extend("pub(crate)".bytes());
-        } else if span_bytes.contains(&i) {
-            ()
-        } else {
+        } else if !span_bytes.contains(&i) {
             new_src.push(b);
         }
     }
@@ -0,0 +1,638 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use quote::ToTokens;
+
+use syn::{spanned::Spanned, visit::Visit};
+
+use crate::{
+    dead_code::{is_dead_use_tree, is_test_module},
+    libs::Lib,
+    scope::{ItemPath, Scopes},
+};
+
+/// An out-of-line `mod foo;` declaration found while walking a source file.
+struct OutOfLineMod {
+    name: String,
+    /// Names of the inline modules enclosing the declaration, outermost first.
+    inline_parents: Vec<String>,
+    /// Value of the `#[path = "..."]` attribute, if any.
+    path_attr: Option<String>,
+    path_attr_span: Option<Range<usize>>,
+    semi_span: Range<usize>,
+}
+
+#[derive(Default)]
+struct OutOfLineModVisitor {
+    inline_parents: Vec<String>,
+    output_mods: Vec<OutOfLineMod>,
+}
+
+impl<'ast> Visit<'ast> for OutOfLineModVisitor {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        if is_test_module(i) {
+            return;
+        }
+        match (&i.content, &i.semi) {
+            (Some(_), _) => {
+                self.inline_parents.push(i.ident.to_string());
+                syn::visit::visit_item_mod(self, i);
+                self.inline_parents.pop();
+            }
+            (None, Some(semi)) => {
+                let path_attr = i.attrs.iter().find(|a| a.path().is_ident("path"));
+                let path_attr_value = path_attr.and_then(|a| match &a.meta {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        value:
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(s),
+                                ..
+                            }),
+                        ..
+                    }) => Some(s.value()),
+                    _ => None,
+                });
+                self.output_mods.push(OutOfLineMod {
+                    name: i.ident.to_string(),
+                    inline_parents: self.inline_parents.clone(),
+                    path_attr: path_attr_value,
+                    path_attr_span: path_attr.map(|a| a.span().byte_range()),
+                    semi_span: semi.span.byte_range(),
+                });
+            }
+            (None, None) => unreachable!("module without content or semicolon"),
+        }
+    }
+}
+
+/// Whether a module file owns its directory, like `lib.rs`, `main.rs`, `mod.rs` or a file
+/// loaded through a `#[path]` attribute.
+fn is_mod_rs(path: &Path, loaded_by_path_attr: bool) -> bool {
+    loaded_by_path_attr
+        || matches!(
+            path.file_name().and_then(|f| f.to_str()),
+            Some("lib.rs" | "main.rs" | "mod.rs")
+        )
+}
+
+fn resolve_mod_path(
+    file: &Path,
+    file_is_mod_rs: bool,
+    m: &OutOfLineMod,
+) -> anyhow::Result<PathBuf> {
+    let file_dir = file
+        .parent()
+        .context("Module file has no parent directory")?;
+    let mut dir = file_dir.to_path_buf();
+    if !file_is_mod_rs && (m.path_attr.is_none() || !m.inline_parents.is_empty()) {
+        dir.push(file.file_stem().context("Module file has no name")?);
+    }
+    dir.extend(&m.inline_parents);
+
+    if let Some(path_attr) = &m.path_attr {
+        return Ok(dir.join(path_attr));
+    }
+    let candidates = [
+        dir.join(format!("{}.rs", m.name)),
+        dir.join(&m.name).join("mod.rs"),
+    ];
+    candidates
+        .iter()
+        .find(|p| p.is_file())
+        .cloned()
+        .with_context(|| {
+            format!(
+                "File for module `{}` not found, tried {} and {}",
+                m.name,
+                candidates[0].display(),
+                candidates[1].display()
+            )
+        })
+}
+
+pub(crate) fn replace_ranges(src: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut new_src = String::with_capacity(src.len());
+    let mut cursor = 0;
+    for (range, replacement) in edits {
+        new_src.push_str(&src[cursor..range.start]);
+        new_src.push_str(&replacement);
+        cursor = range.end;
+    }
+    new_src.push_str(&src[cursor..]);
+    new_src
+}
+
+/// Reads a module file and recursively inlines every out-of-line `mod` declaration in it.
+/// Every file read is added to `sources`.
+fn expand_module_file(
+    path: &Path,
+    file_is_mod_rs: bool,
+    sources: &mut Vec<PathBuf>,
+) -> anyhow::Result<String> {
+    sources.push(path.to_path_buf());
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read module file {}", path.display()))?;
+    let ast = syn::parse_file(&src)
+        .with_context(|| format!("Failed to parse module file {}", path.display()))?;
+    let mut visitor = OutOfLineModVisitor::default();
+    visitor.visit_file(&ast);
+
+    let mut edits = Vec::new();
+    for m in visitor.output_mods {
+        let mod_path = resolve_mod_path(path, file_is_mod_rs, &m)?;
+        let mod_is_mod_rs = is_mod_rs(&mod_path, m.path_attr.is_some());
+        let contents = expand_module_file(&mod_path, mod_is_mod_rs, sources)?;
+        if let Some(path_attr_span) = m.path_attr_span {
+            edits.push((path_attr_span, String::new()));
+        }
+        edits.push((m.semi_span, format!(" {{\n{contents}\n}}")));
+    }
+
+    Ok(replace_ranges(&src, edits))
+}
+
+/// The first segment of a path, like `crate` in `crate::foo::Bar` or `crads` in `use crads::Scanner`.
+struct PathRoot {
+    ident: String,
+    ident_span: Range<usize>,
+    leading_colon_span: Option<Range<usize>>,
+}
+
+#[derive(Default)]
+struct PathRootVisitor {
+    output_roots: Vec<PathRoot>,
+}
+
+impl PathRootVisitor {
+    fn push(&mut self, ident: &syn::Ident, leading_colon: Option<&syn::token::PathSep>) {
+        self.output_roots.push(PathRoot {
+            ident: ident.to_string(),
+            ident_span: ident.span().byte_range(),
+            leading_colon_span: leading_colon.map(|c| c.span().byte_range()),
+        });
+    }
+
+    fn visit_use_tree_root(
+        &mut self,
+        tree: &syn::UseTree,
+        leading_colon: Option<&syn::token::PathSep>,
+    ) {
+        match tree {
+            syn::UseTree::Path(syn::UsePath { ident, .. })
+            | syn::UseTree::Name(syn::UseName { ident })
+            | syn::UseTree::Rename(syn::UseRename { ident, .. }) => self.push(ident, leading_colon),
+            syn::UseTree::Glob(_) => (),
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.visit_use_tree_root(tree, None);
+                }
+            }
+        }
+    }
+
+    /// Macro bodies are opaque token streams, so look for `name ::` and `$crate ::` by hand.
+    fn visit_tokens(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::TokenTree;
+
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.visit_tokens(group.stream()),
+                TokenTree::Ident(ident) => {
+                    let followed_by_path_sep = matches!(
+                        (tokens.get(i + 1), tokens.get(i + 2)),
+                        (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                            if a.as_char() == ':'
+                                && a.spacing() == proc_macro2::Spacing::Joint
+                                && b.as_char() == ':'
+                    );
+                    // Skip `a::name`, `x.name::<T>()` and metavariables like `$name`, but keep `$crate`.
+                    let preceded_by_punct = i.checked_sub(1).and_then(|j| match &tokens[j] {
+                        TokenTree::Punct(p) => Some(p.as_char()),
+                        _ => None,
+                    });
+                    let is_root = match preceded_by_punct {
+                        Some(':' | '.') => false,
+                        Some('$') => ident == "crate",
+                        _ => true,
+                    };
+                    if followed_by_path_sep && is_root {
+                        self.push(ident, None);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for PathRootVisitor {
+    fn visit_path(&mut self, i: &'ast syn::Path) {
+        // Single segment paths are plain names like `pub(crate)` or a local variable.
+        if i.segments.len() > 1 {
+            self.push(&i.segments[0].ident, i.leading_colon.as_ref());
+        }
+        syn::visit::visit_path(self, i);
+    }
+
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        self.visit_use_tree_root(&i.tree, i.leading_colon.as_ref());
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        self.visit_tokens(i.tokens.clone());
+        syn::visit::visit_macro(self, i);
+    }
+}
+
+fn path_roots(src: &str) -> anyhow::Result<Vec<PathRoot>> {
+    let ast = syn::parse_file(src)?;
+    let mut visitor = PathRootVisitor::default();
+    visitor.visit_file(&ast);
+    Ok(visitor.output_roots)
+}
+
+/// Rewrites the paths of a library so they keep working once it is inlined as
+/// `mod name { ... }` into the binary crate: `crate::` and `$crate::` point at the library
+/// module, and paths into the other bundled libraries in `deps` start from the crate root.
+fn rewrite_lib_paths(src: &str, roots: &[PathRoot], name: &str, deps: &BTreeSet<String>) -> String {
+    let mut edits = Vec::new();
+    for root in roots {
+        if root.ident == "crate" {
+            let end = root.ident_span.end;
+            edits.push((end..end, format!("::{name}")));
+        } else if deps.contains(&root.ident) {
+            match &root.leading_colon_span {
+                Some(leading_colon_span) => {
+                    edits.push((leading_colon_span.clone(), "crate::".to_string()))
+                }
+                None => {
+                    let start = root.ident_span.start;
+                    edits.push((start..start, "crate::".to_string()));
+                }
+            }
+        }
+    }
+    replace_ranges(src, edits)
+}
+
+/// Names of the libraries in `libs` that are referenced by a path in the given source.
+fn referenced_libs(
+    roots: &[PathRoot],
+    libs: &BTreeMap<String, Lib>,
+    name: Option<&str>,
+) -> BTreeSet<String> {
+    roots
+        .iter()
+        .map(|root| &root.ident)
+        .filter(|ident| libs.contains_key(*ident) && Some(ident.as_str()) != name)
+        .cloned()
+        .collect()
+}
+
+/// Finds `feature = "..."` predicates in `#[cfg]`, `#[cfg_attr]` and `cfg!`.
+#[derive(Default)]
+struct FeatureCfgVisitor {
+    output_features: Vec<(String, Range<usize>)>,
+}
+
+impl FeatureCfgVisitor {
+    fn visit_tokens(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::TokenTree;
+
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.visit_tokens(group.stream()),
+                TokenTree::Ident(ident) if ident == "feature" => {
+                    if let (Some(TokenTree::Punct(eq)), Some(TokenTree::Literal(lit))) =
+                        (tokens.get(i + 1), tokens.get(i + 2))
+                    {
+                        if eq.as_char() == '=' {
+                            if let Ok(lit) = syn::parse_str::<syn::LitStr>(&lit.to_string()) {
+                                let range = ident.span().byte_range().start
+                                    ..tokens[i + 2].span().byte_range().end;
+                                self.output_features.push((lit.value(), range));
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for FeatureCfgVisitor {
+    fn visit_attribute(&mut self, i: &'ast syn::Attribute) {
+        if i.path().is_ident("cfg") || i.path().is_ident("cfg_attr") {
+            if let syn::Meta::List(meta_list) = &i.meta {
+                self.visit_tokens(meta_list.tokens.clone());
+            }
+        }
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        if i.path.is_ident("cfg") {
+            self.visit_tokens(i.tokens.clone());
+        }
+        syn::visit::visit_macro(self, i);
+    }
+}
+
+/// Resolves the `feature = "..."` predicates of a library against its enabled features,
+/// since once inlined they would be checked against the binary's features instead.
+/// Enabled features become `all()` and disabled ones `any()`, which rustc reads as true and false.
+fn resolve_feature_cfgs(src: &str, features: &BTreeSet<String>) -> anyhow::Result<String> {
+    let ast = syn::parse_file(src)?;
+    let mut visitor = FeatureCfgVisitor::default();
+    visitor.visit_file(&ast);
+    let edits = visitor
+        .output_features
+        .into_iter()
+        .map(|(feature, range)| {
+            let predicate = if features.contains(&feature) {
+                "all()"
+            } else {
+                "any()"
+            };
+            (range, predicate.to_string())
+        })
+        .collect();
+    Ok(replace_ranges(src, edits))
+}
+
+/// Finds the `#[macro_export]` macros of a library, wherever they are defined in it.
+#[derive(Default)]
+struct ExportedMacroVisitor {
+    output_names: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for ExportedMacroVisitor {
+    fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+        if let Some(ident) = &i.ident {
+            if i.attrs.iter().any(|a| a.path().is_ident("macro_export")) {
+                self.output_names.push(ident.to_string());
+            }
+        }
+    }
+}
+
+/// Finds the paths to macros exported by the bundled libraries, which rustc places at the root
+/// of the crate they are defined in. Once a library is inlined that is the root of the bundle,
+/// so `crads::name!` has to become `crate::name!`.
+struct ExportedMacroPathVisitor<'a> {
+    /// Library each exported macro comes from, by name
+    exported_macros: &'a BTreeMap<String, String>,
+    scopes: &'a Scopes,
+    module: ItemPath,
+    output_edits: Vec<(Range<usize>, String)>,
+}
+
+impl ExportedMacroPathVisitor<'_> {
+    /// Whether a path made of `prefix` and `name` refers to a macro exported by a library.
+    fn is_exported_macro(&self, prefix: &[String], name: &str) -> bool {
+        self.exported_macros.get(name).is_some_and(|lib| {
+            !prefix.is_empty()
+                && self.scopes.resolve_segments(&self.module, prefix) == Some(vec![lib.clone()])
+        })
+    }
+
+    /// Looks for paths like `crads::name!` and `$crate::crads::name!` in macro bodies.
+    fn visit_tokens(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::TokenTree;
+
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let mut path: Vec<(String, Range<usize>)> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                TokenTree::Group(group) => {
+                    self.visit_tokens(group.stream());
+                    path.clear();
+                }
+                TokenTree::Ident(ident) => {
+                    let range = ident.span().byte_range();
+                    let ident = ident.to_string();
+                    let is_crate = ident == "crate"
+                        && matches!(i.checked_sub(1).map(|j| &tokens[j]), Some(TokenTree::Punct(p)) if p.as_char() == '$');
+                    if is_crate {
+                        path = vec![("$crate".to_string(), tokens[i - 1].span().byte_range())];
+                    } else {
+                        path.push((ident, range));
+                    }
+                    match (tokens.get(i + 1), tokens.get(i + 2)) {
+                        (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                            if a.as_char() == ':' && b.as_char() == ':' =>
+                        {
+                            i += 2;
+                        }
+                        (Some(TokenTree::Punct(bang)), _) if bang.as_char() == '!' => {
+                            self.visit_token_path(&path);
+                            path.clear();
+                        }
+                        _ => path.clear(),
+                    }
+                }
+                _ => path.clear(),
+            }
+            i += 1;
+        }
+    }
+
+    fn visit_token_path(&mut self, path: &[(String, Range<usize>)]) {
+        let Some(((name, name_range), prefix)) = path.split_last() else {
+            return;
+        };
+        let segments: Vec<String> = prefix
+            .iter()
+            .map(|(s, _)| {
+                if s == "$crate" {
+                    "crate".to_string()
+                } else {
+                    s.clone()
+                }
+            })
+            .collect();
+        if self.is_exported_macro(&segments, name) {
+            let root = if prefix[0].0 == "$crate" {
+                "$crate::"
+            } else {
+                "crate::"
+            };
+            self.output_edits
+                .push((prefix[0].1.start..name_range.start, root.to_string()));
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ExportedMacroPathVisitor<'_> {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        let segments: Vec<String> = i
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect();
+        if let Some((name, prefix)) = segments.split_last() {
+            if i.path.leading_colon.is_none() && self.is_exported_macro(prefix, name) {
+                let start = i.path.span().byte_range().start;
+                let end = i.path.segments.last().unwrap().span().byte_range().start;
+                self.output_edits.push((start..end, "crate::".to_string()));
+            }
+        }
+        self.visit_tokens(i.tokens.clone());
+    }
+
+    // An exported macro can't be imported into the root next to itself, and a group like
+    // `crads::{name, Scanner}` can't import it from the root, so such imports are moved out
+    // into their own `use crate::name;`.
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        if i.leading_colon.is_some() {
+            return;
+        }
+        let moved = RefCell::new(Vec::new());
+        let is_moved = |tree: &syn::UseTree, prefix: &[String]| {
+            let (ident, rename) = match tree {
+                syn::UseTree::Name(use_name) => (&use_name.ident, None),
+                syn::UseTree::Rename(use_rename) => (&use_rename.ident, Some(&use_rename.rename)),
+                _ => return false,
+            };
+            let is_exported = self.is_exported_macro(prefix, &ident.to_string());
+            if is_exported {
+                moved.borrow_mut().push(match rename {
+                    Some(rename) => format!("{ident} as {rename}"),
+                    None => ident.to_string(),
+                });
+            }
+            is_exported
+        };
+        let mut moved_ranges = Vec::new();
+        let is_all_moved = is_dead_use_tree(&i.tree, &mut Vec::new(), &is_moved, &mut moved_ranges);
+        let moved = moved.into_inner();
+        if moved.is_empty() {
+            return;
+        }
+        let vis = match &i.vis {
+            syn::Visibility::Inherited => String::new(),
+            vis => format!("{} ", vis.to_token_stream()),
+        };
+        let imports: Vec<String> = moved
+            .iter()
+            .filter(|import| !self.module.is_empty() || import.contains(" as "))
+            .map(|import| format!("{vis}use crate::{import};"))
+            .collect();
+        let range = i.span().byte_range();
+        if is_all_moved {
+            self.output_edits.push((range, imports.join("\n")));
+        } else {
+            self.output_edits
+                .extend(moved_ranges.into_iter().map(|r| (r, String::new())));
+            let imports: String = imports.iter().map(|import| format!("\n{import}")).collect();
+            self.output_edits.push((range.end..range.end, imports));
+        }
+    }
+}
+
+/// Rewrites the paths to macros exported by the bundled libraries so they point at the root
+/// of the bundle, where the inlined `#[macro_export]` macros end up.
+fn rewrite_exported_macro_paths(
+    src: &str,
+    exported_macros: &BTreeMap<String, String>,
+) -> anyhow::Result<String> {
+    if exported_macros.is_empty() {
+        return Ok(src.to_string());
+    }
+    let ast = syn::parse_file(src)?;
+    let scopes = Scopes::new(&ast);
+    let mut visitor = ExportedMacroPathVisitor {
+        exported_macros,
+        scopes: &scopes,
+        module: Vec::new(),
+        output_edits: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+    Ok(replace_ranges(src, visitor.output_edits))
+}
+
+/// Reads the root file of a crate and returns its source with the whole module tree inlined.
+pub fn expand_crate(root: &Path) -> anyhow::Result<String> {
+    expand_module_file(root, true, &mut Vec::new())
+}
+
+/// Appends to `src` the libraries it references, directly or through other libraries.
+/// Libraries are inlined as modules, with each library placed after the libraries it uses.
+pub fn expand_libs(libs: &BTreeMap<String, Lib>, src: String) -> anyhow::Result<String> {
+    Ok(expand_libs_with_sources(libs, src)?.0)
+}
+
+/// Like [`expand_libs`], also returning the library files that were inlined.
+pub fn expand_libs_with_sources(
+    libs: &BTreeMap<String, Lib>,
+    src: String,
+) -> anyhow::Result<(String, Vec<PathBuf>)> {
+    let mut queue: Vec<String> = referenced_libs(&path_roots(&src)?, libs, None)
+        .into_iter()
+        .collect();
+    let mut lib_modules: BTreeMap<String, (String, BTreeSet<String>)> = BTreeMap::new();
+    let mut exported_macros: BTreeMap<String, String> = BTreeMap::new();
+    let mut sources = Vec::new();
+    while let Some(name) = queue.pop() {
+        if lib_modules.contains_key(&name) {
+            continue;
+        }
+        let contents = expand_module_file(&libs[&name].src_path, true, &mut sources)?;
+        let contents = resolve_feature_cfgs(&contents, &libs[&name].features)?;
+        let mut macro_visitor = ExportedMacroVisitor::default();
+        macro_visitor.visit_file(&syn::parse_file(&contents)?);
+        for macro_name in macro_visitor.output_names {
+            if let Some(other) = exported_macros.insert(macro_name.clone(), name.clone()) {
+                anyhow::ensure!(
+                    other == name,
+                    "Libraries {other} and {name} both export a macro named `{macro_name}`"
+                );
+            }
+        }
+        let roots = path_roots(&contents)?;
+        let deps = referenced_libs(&roots, libs, Some(&name));
+        let contents = rewrite_lib_paths(&contents, &roots, &name, &deps);
+        let module = format!("mod {name} {{\n{}\n}}", contents);
+        queue.extend(deps.iter().cloned());
+        lib_modules.insert(name, (module, deps));
+    }
+
+    let mut bundle = src;
+    let mut emitted: BTreeSet<String> = BTreeSet::new();
+    while emitted.len() < lib_modules.len() {
+        let next = lib_modules
+            .iter()
+            .find(|(name, (_, deps))| {
+                !emitted.contains(*name) && deps.iter().all(|d| emitted.contains(d))
+            })
+            // Libraries that use each other can go in any order.
+            .or_else(|| {
+                lib_modules
+                    .iter()
+                    .find(|(name, _)| !emitted.contains(*name))
+            })
+            .map(|(name, (module, _))| (name.clone(), module));
+        let (name, module) = next.expect("some library is not emitted yet");
+        bundle.push('\n');
+        bundle.push_str(module);
+        emitted.insert(name);
+    }
+    let bundle = rewrite_exported_macro_paths(&bundle, &exported_macros)?;
+    Ok((bundle, sources))
+}
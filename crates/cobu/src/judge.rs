@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use cargo_platform::Cfg;
+use serde_json::Value;
+
+use crate::cfg::{default_target_cfg, override_target_cfg};
+
+/// Edition used when neither the judge nor the package says otherwise.
+pub const DEFAULT_EDITION: &str = "2021";
+
+/// How a judge compiles submissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JudgeProfile {
+    pub name: String,
+    /// Edition the judge compiles with, or `None` to use the package's
+    pub edition: Option<String>,
+    /// Version of rustc the judge runs, like `1.75.0`
+    pub rust_version: Option<String>,
+    /// crates.io crates the judge provides, or `None` if that is unknown
+    pub allowed_crates: Option<Vec<String>>,
+    /// Maximum size of a submission in bytes
+    pub size_limit: Option<usize>,
+    /// cfg set that `#[cfg]` is evaluated against
+    pub target_cfg: Vec<Cfg>,
+}
+
+impl Default for JudgeProfile {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            edition: None,
+            rust_version: None,
+            allowed_crates: None,
+            size_limit: None,
+            target_cfg: default_target_cfg(),
+        }
+    }
+}
+
+fn linux_target_cfg() -> Vec<Cfg> {
+    let overrides: Vec<Cfg> = ["target_family = \"unix\"", "target_os = \"linux\""]
+        .into_iter()
+        .map(|s| Cfg::from_str(s).expect("valid linux cfg"))
+        .collect();
+    override_target_cfg(default_target_cfg(), &overrides)
+}
+
+/// Names of the built-in judge profiles.
+pub const JUDGE_NAMES: [&str; 4] = ["default", "codeforces", "atcoder", "kattis"];
+
+/// Returns the built-in profile of a judge.
+pub fn judge_profile(name: &str) -> anyhow::Result<JudgeProfile> {
+    let profile = match name {
+        "default" => JudgeProfile::default(),
+        "codeforces" => JudgeProfile {
+            name: name.to_string(),
+            edition: Some("2021".to_string()),
+            rust_version: Some("1.75.0".to_string()),
+            allowed_crates: Some(Vec::new()),
+            size_limit: Some(65536),
+            target_cfg: default_target_cfg(),
+        },
+        "atcoder" => JudgeProfile {
+            name: name.to_string(),
+            edition: Some("2021".to_string()),
+            rust_version: Some("1.70.0".to_string()),
+            allowed_crates: Some(
+                [
+                    "ac-library-rs",
+                    "itertools",
+                    "num",
+                    "proconio",
+                    "rand",
+                    "superslice",
+                ]
+                .map(String::from)
+                .to_vec(),
+            ),
+            size_limit: Some(512 * 1024),
+            target_cfg: linux_target_cfg(),
+        },
+        "kattis" => JudgeProfile {
+            name: name.to_string(),
+            edition: Some("2021".to_string()),
+            rust_version: None,
+            allowed_crates: Some(Vec::new()),
+            size_limit: None,
+            target_cfg: linux_target_cfg(),
+        },
+        _ => anyhow::bail!(
+            "Unknown judge {name}, expected one of {}",
+            JUDGE_NAMES.join(", ")
+        ),
+    };
+    Ok(profile)
+}
+
+fn string_list(value: &Value, key: &str) -> anyhow::Result<Vec<String>> {
+    value
+        .as_array()
+        .and_then(|a| a.iter().map(|v| v.as_str().map(String::from)).collect())
+        .with_context(|| format!("`{key}` must be a list of strings"))
+}
+
+fn string(value: &Value, key: &str) -> anyhow::Result<String> {
+    value
+        .as_str()
+        .map(String::from)
+        .with_context(|| format!("`{key}` must be a string"))
+}
+
+/// Picks the profile of `judge`, or of the `judge` key of `[package.metadata.cobu]` if not
+/// given, and applies the other keys of that table on top of it.
+pub fn package_judge_profile(
+    package_metadata: &Value,
+    judge: Option<&str>,
+) -> anyhow::Result<JudgeProfile> {
+    let table = &package_metadata["cobu"];
+    let judge = match (judge, &table["judge"]) {
+        (Some(judge), _) => judge.to_string(),
+        (None, Value::Null) => "default".to_string(),
+        (None, value) => string(value, "judge")?,
+    };
+    let mut profile = judge_profile(&judge)?;
+    let Some(table) = table.as_object() else {
+        return Ok(profile);
+    };
+    for (key, value) in table {
+        match key.as_str() {
+            "judge" => (),
+            "edition" => profile.edition = Some(string(value, key)?),
+            "rust-version" => profile.rust_version = Some(string(value, key)?),
+            "allowed-crates" => profile.allowed_crates = Some(string_list(value, key)?),
+            "size-limit" => {
+                let size_limit = value.as_u64().context("`size-limit` must be a number")?;
+                profile.size_limit = Some(size_limit.try_into()?);
+            }
+            "cfg" => {
+                let overrides: Vec<Cfg> = string_list(value, key)?
+                    .iter()
+                    .map(|s| Cfg::from_str(s))
+                    .collect::<Result<_, _>>()?;
+                profile.target_cfg = override_target_cfg(profile.target_cfg, &overrides);
+            }
+            _ => anyhow::bail!("Unknown key `{key}` in [package.metadata.cobu]"),
+        }
+    }
+    Ok(profile)
+}
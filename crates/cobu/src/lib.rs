@@ -1,14 +1,55 @@
-use std::{collections::BTreeMap, error::Error, io::Write, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::Context;
 
-use cargo_metadata::Target;
-use clap::Parser;
+use cargo_metadata::{DependencyKind, Target};
+use clap::{Parser, Subcommand};
+
+mod cache;
+mod check;
+use cache::BundleCache;
 
 mod dead_code;
-pub use dead_code::remove_dead_code;
+pub use dead_code::{
+    remove_dead_code, remove_dead_code_with_stats, remove_lib_docs, DeadCodeStats,
+};
+
+mod expand;
+pub use expand::{expand_crate, expand_libs, expand_libs_with_sources};
+
+mod judge;
+pub use judge::{judge_profile, package_judge_profile, JudgeProfile, DEFAULT_EDITION, JUDGE_NAMES};
+
+mod libs;
+pub use libs::{discover_libs, Lib};
+
+mod cfg;
+pub use cargo_platform::Cfg;
+pub use cfg::{default_target_cfg, override_target_cfg, strip_cfgs};
+
+mod macros;
+pub use macros::expand_macro_rules;
 
-use crate::dead_code::{remove_tests, replace_pub_with_pub_crate};
+mod minify;
+pub use minify::minify;
+
+mod reach;
+mod rust_version;
+pub use rust_version::{newer_features, NewerFeature};
+mod scope;
+mod verify;
+
+use crate::{
+    check::{check_bundle, check_cached_rust_version, check_rust_version},
+    dead_code::{remove_tests, replace_pub_with_pub_crate},
+    minify::check_size_limit,
+};
 
 /// COmpetitive BUndler for Rust
 #[derive(Parser, Debug, Default)]
@@ -25,12 +66,94 @@ pub struct Args {
     #[arg(long, value_hint = clap::ValueHint::FilePath)]
     pub manifest_path: Option<PathBuf>,
 
+    /// Libraries to bundle as `name=path/to/lib.rs` pairs.
+    /// Overrides the libraries discovered from the package's path dependencies.
     #[arg(long, value_parser = parse_key_val::<String, PathBuf>, value_delimiter = ',')]
     pub libs: Vec<(String, PathBuf)>,
 
+    /// crates.io dependencies to bundle, by package name
+    #[arg(long, value_delimiter = ',')]
+    pub registry_libs: Vec<String>,
+
+    /// Directory created by `cargo vendor` to take the registry dependencies from
+    #[arg(long, value_hint = clap::ValueHint::DirPath)]
+    pub vendor_dir: Option<PathBuf>,
+
+    /// Run cargo metadata without accessing the network
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Judge to bundle for: default, codeforces, atcoder or kattis. Sets the edition, cfgs and
+    /// size limit. Defaults to the `judge` key of `[package.metadata.cobu]`
+    #[arg(long)]
+    pub judge: Option<String>,
+
+    /// Version of Rust the judge runs, like `1.70.0`. Defaults to the judge profile's version
+    #[arg(long)]
+    pub rust_version: Option<String>,
+
+    /// cfg to set for the judge, like `target_os="linux"` or `debug_assertions`.
+    /// Replaces the default values of the same key
+    #[arg(long = "cfg", value_name = "SPEC")]
+    pub cfgs: Vec<Cfg>,
+
+    /// Also remove struct fields that are never read and enum variants that are never constructed
+    #[arg(long)]
+    pub remove_dead_fields: bool,
+
+    /// Expand local `macro_rules!` invocations in item position, so the items they generate can
+    /// be removed one by one
+    #[arg(long)]
+    pub expand_macros: bool,
+
+    /// Remove docs, comments and cosmetic attributes like `#[must_use]` from the inlined
+    /// libraries, keeping the ones in the binary
+    #[arg(long)]
+    pub strip_lib_docs: bool,
+
+    /// Remove docs, comments and cosmetic attributes like `#[must_use]` and `#[inline]` from
+    /// the bundle
+    #[arg(long)]
+    pub minify: bool,
+
+    /// Don't format the bundle with rustfmt. Together with `--minify`, each top level item
+    /// is put on a single line
+    #[arg(long)]
+    pub no_rustfmt: bool,
+
+    /// Fail if a bundle is larger than this many bytes, like the 65536 of Codeforces
+    #[arg(long, value_name = "BYTES")]
+    pub size_limit: Option<usize>,
+
+    /// Also write the bundle as it is before dead code is removed, as `<bin>.unminimized.rs`
+    #[arg(long)]
+    pub keep_unminimized: bool,
+
+    /// Bundle every binary, even the ones whose sources did not change since the last run
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Print `cargo:rerun-if-changed` for the files that feed each bundle, for build scripts
+    #[arg(long)]
+    pub rerun_if_changed: bool,
+
     /// Output directory path
     #[arg(short, long)]
     pub out_dir: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Bundle, then run the original and the bundled binaries on the same inputs and compare
+    /// what they print
+    Verify {
+        /// Directory with the inputs of each binary, as `<bin>/*.in`. Relative to the package
+        #[arg(long, default_value = "tests", value_hint = clap::ValueHint::DirPath)]
+        tests_dir: PathBuf,
+    },
 }
 
 /// Copied from https://github.com/clap-rs/clap/blob/2920fb082c987acb72ed1d1f47991c4d157e380d/examples/typed-derive.rs#L48
@@ -47,8 +170,9 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
-pub fn rustfmt(src: &str) -> anyhow::Result<String> {
+pub fn rustfmt(src: &str, edition: &str) -> anyhow::Result<String> {
     let mut command = std::process::Command::new("rustfmt")
+        .args(["--edition", edition])
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .spawn()?;
@@ -67,31 +191,76 @@ pub fn rustfmt(src: &str) -> anyhow::Result<String> {
     Ok(stdout_string)
 }
 
-fn expand_libs(libs: &BTreeMap<String, PathBuf>, src: String) -> Result<String, std::io::Error> {
-    std::iter::once(Ok(src))
-        .chain(libs.iter().map(|(name, path)| {
-            std::fs::read_to_string(path).map(|contents| format!("mod {name} {{\n{}\n}}", contents))
-        }))
-        .collect()
+/// Options for [`minimize_code`].
+#[derive(Debug, Clone)]
+pub struct MinimizeOptions {
+    /// cfg set that `#[cfg]`, `#[cfg_attr]` and `cfg!` are evaluated against
+    pub target_cfg: Vec<Cfg>,
+    /// Also remove struct fields that are never read and enum variants that are never
+    /// constructed
+    pub remove_dead_fields: bool,
+    /// Expand local `macro_rules!` invocations in item position before removing dead code
+    pub expand_macros: bool,
+    /// Remove docs, comments and cosmetic attributes after removing dead code
+    pub minify: bool,
+    /// Leave the code unformatted instead of running rustfmt on it
+    pub skip_rustfmt: bool,
+    /// Edition rustc checks the code with
+    pub edition: String,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        Self {
+            target_cfg: default_target_cfg(),
+            remove_dead_fields: false,
+            expand_macros: false,
+            minify: false,
+            skip_rustfmt: false,
+            edition: DEFAULT_EDITION.to_string(),
+        }
+    }
+}
+
+pub fn minimize_code(src: String, options: &MinimizeOptions) -> anyhow::Result<String> {
+    Ok(minimize_code_with_stats(src, options)?.0)
 }
 
-pub fn minimize_code(src: String) -> anyhow::Result<String> {
+/// Like [`minimize_code`], also reporting what removing dead code took.
+pub fn minimize_code_with_stats(
+    src: String,
+    options: &MinimizeOptions,
+) -> anyhow::Result<(String, DeadCodeStats)> {
     let src = replace_pub_with_pub_crate(src)?;
+    let src = strip_cfgs(src, &options.target_cfg)?;
     let src = remove_tests(src)?;
-    let src = remove_dead_code(src)?;
-    let src = rustfmt(&src)?;
-    Ok(src)
+    let src = if options.expand_macros {
+        // Expansions can have `pub` items, cfgs and tests of their own
+        let src = replace_pub_with_pub_crate(expand_macro_rules(src)?)?;
+        remove_tests(strip_cfgs(src, &options.target_cfg)?)?
+    } else {
+        src
+    };
+    let (src, stats) =
+        remove_dead_code_with_stats(src, options.remove_dead_fields, &options.edition)?;
+    let src = if options.minify { minify(src)? } else { src };
+    let src = if options.skip_rustfmt {
+        src
+    } else {
+        rustfmt(&src, &options.edition)?
+    };
+    Ok((src, stats))
 }
 
-pub fn cli(args: Args) -> anyhow::Result<()> {
-    let libs: BTreeMap<String, PathBuf> = args.libs.clone().into_iter().collect();
-    assert_eq!(libs.len(), args.libs.len(), "no duplicate lib names");
-
+pub fn cli(mut args: Args) -> anyhow::Result<()> {
     let cwd_manifest_path = std::env::current_dir()?.join("Cargo.toml");
     let manifest_path = args.manifest_path.as_ref().unwrap_or(&cwd_manifest_path);
-    let metadata = cargo_metadata::MetadataCommand::new()
-        .manifest_path(manifest_path)
-        .exec()?;
+    let mut metadata_command = cargo_metadata::MetadataCommand::new();
+    metadata_command.manifest_path(manifest_path);
+    if args.offline {
+        metadata_command.other_options(vec!["--offline".to_string()]);
+    }
+    let metadata = metadata_command.exec()?;
 
     let package = if let Some(package) = &args.package {
         metadata
@@ -103,6 +272,45 @@ pub fn cli(args: Args) -> anyhow::Result<()> {
         metadata.root_package().context("Root package not found")?
     };
 
+    let profile = package_judge_profile(&package.metadata, args.judge.as_deref())?;
+    args.size_limit = args.size_limit.or(profile.size_limit);
+    args.rust_version = args.rust_version.or(profile.rust_version.clone());
+
+    let libs: BTreeMap<String, Lib> = if args.libs.is_empty() {
+        discover_libs(
+            &metadata,
+            package,
+            &args.registry_libs,
+            args.vendor_dir.as_deref(),
+        )?
+    } else {
+        let libs: BTreeMap<String, Lib> = args
+            .libs
+            .iter()
+            .map(|(name, path)| (name.clone(), Lib::new(path.clone())))
+            .collect();
+        assert_eq!(libs.len(), args.libs.len(), "no duplicate lib names");
+        libs
+    };
+
+    if let Some(allowed_crates) = &profile.allowed_crates {
+        let normalize = |name: &str| name.replace('-', "_");
+        for dependency in &package.dependencies {
+            let name = normalize(dependency.rename.as_ref().unwrap_or(&dependency.name));
+            let is_available = dependency.path.is_some()
+                || dependency.kind != DependencyKind::Normal
+                || libs.contains_key(&name)
+                || allowed_crates.iter().any(|c| normalize(c) == name);
+            anyhow::ensure!(
+                is_available,
+                "Judge {} doesn't provide the crate {}, bundle it with --registry-libs {}",
+                profile.name,
+                dependency.name,
+                dependency.name
+            );
+        }
+    }
+
     let bins: Vec<Target> = package
         .targets
         .iter()
@@ -110,31 +318,208 @@ pub fn cli(args: Args) -> anyhow::Result<()> {
         .cloned()
         .collect();
 
-    let bins: Vec<Target> = if let Some(bin) = args.bin {
+    let bins: Vec<Target> = if let Some(bin) = &args.bin {
         vec![bins
             .into_iter()
-            .find(|t| t.name == bin)
+            .find(|t| t.name == *bin)
             .context(format!("Binary {} not found", bin))?]
     } else {
         bins
     };
 
+    let package_features = metadata
+        .resolve
+        .as_ref()
+        .map(|resolve| resolve[&package.id].features.clone())
+        .unwrap_or_default();
+    let feature_cfgs: Vec<Cfg> = package_features
+        .iter()
+        .map(|f| Cfg::from_str(&format!("feature = \"{f}\"")))
+        .collect::<Result<_, _>>()?;
+    let options = MinimizeOptions {
+        target_cfg: override_target_cfg(profile.target_cfg.clone(), &args.cfgs)
+            .into_iter()
+            .chain(feature_cfgs)
+            .collect(),
+        remove_dead_fields: args.remove_dead_fields,
+        expand_macros: args.expand_macros,
+        minify: args.minify,
+        skip_rustfmt: args.no_rustfmt,
+        edition: profile
+            .edition
+            .clone()
+            .unwrap_or_else(|| package.edition.as_str().to_string()),
+    };
+
     if !args.out_dir.exists() {
         std::fs::create_dir(&args.out_dir)?;
     }
     assert!(args.out_dir.is_dir());
-    for bin in bins {
-        let src = std::fs::read_to_string(bin.src_path)?;
-        let src = expand_libs(&libs, src)?;
-        let src = minimize_code(src)?;
-        let src = format!("// Bundled by cobu (https://github.com/skrobchik/cobu)\n{src}");
-        let mut f = std::fs::File::create(args.out_dir.join(bin.name).with_extension("rs"))?;
-        f.write_all(src.as_bytes())?;
+
+    let cache_key = format!(
+        "{options:?} {libs:?} {:?} {:?} {} {} {}",
+        args.size_limit,
+        args.rust_version,
+        args.strip_lib_docs,
+        args.keep_unminimized,
+        cache::rustc_version()
+    );
+    let mut cache = BundleCache::load(
+        metadata.target_directory.as_std_path(),
+        &package.name,
+        &args.out_dir,
+    );
+    let mut sources: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut stale_bins = Vec::new();
+    for bin in &bins {
+        let fresh_sources = cache
+            .fresh_sources(&bin.name, &cache_key)
+            .filter(|_| !args.no_cache && bundle_path(&args.out_dir, bin).is_file());
+        if let Some(fresh_sources) = fresh_sources {
+            eprintln!("Bundle of {} is up to date", bin.name);
+            if let Some(rust_version) = &args.rust_version {
+                check_cached_rust_version(
+                    &bundle_path(&args.out_dir, bin),
+                    rust_version,
+                    fresh_sources,
+                    args.rerun_if_changed,
+                )?;
+            }
+            sources.insert(bin.name.clone(), fresh_sources.to_vec());
+        } else {
+            stale_bins.push(bin);
+        }
+    }
+
+    // Most of the time goes into rustc and rustfmt, which run as separate processes.
+    let results: Vec<anyhow::Result<Vec<PathBuf>>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = stale_bins
+            .iter()
+            .map(|bin| scope.spawn(|| bundle_bin(bin, &libs, &options, &args)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("bundling thread panicked"))
+            .collect()
+    });
+
+    let mut first_error = None;
+    for (bin, result) in stale_bins.into_iter().zip(results) {
+        match result {
+            Ok(bin_sources) => {
+                cache.insert(bin.name.clone(), &cache_key, bin_sources.clone());
+                sources.insert(bin.name.clone(), bin_sources);
+            }
+            Err(e) => {
+                let e = e.context(format!("Failed to bundle {}", bin.name));
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    cache.save()?;
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+
+    if let Some(Command::Verify { tests_dir }) = &args.command {
+        let package_dir = package
+            .manifest_path
+            .parent()
+            .context("Manifest path has no parent directory")?;
+        verify::verify_bins(
+            package.manifest_path.as_std_path(),
+            &package.name,
+            &bins,
+            &options.edition,
+            &args.out_dir,
+            &package_dir.as_std_path().join(tests_dir),
+            metadata.target_directory.as_std_path(),
+        )?;
+    }
+
+    if args.rerun_if_changed {
+        let sources: BTreeSet<&PathBuf> = sources.values().flatten().collect();
+        for source in sources {
+            println!("cargo:rerun-if-changed={}", source.display());
+        }
     }
 
     Ok(())
 }
 
+fn bundle_path(out_dir: &Path, bin: &Target) -> PathBuf {
+    out_dir.join(&bin.name).with_extension("rs")
+}
+
+/// Bundles `bin` into `out_dir`, returning the files the bundle was made from.
+fn bundle_bin(
+    bin: &Target,
+    libs: &BTreeMap<String, Lib>,
+    options: &MinimizeOptions,
+    args: &Args,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let src = std::fs::read_to_string(&bin.src_path)?;
+    let (src, lib_sources) = expand_libs_with_sources(libs, src)?;
+    let mut sources = vec![bin.src_path.clone().into_std_path_buf()];
+    sources.extend(lib_sources);
+    let src = if args.strip_lib_docs {
+        remove_lib_docs(src, &libs.keys().cloned().collect())?
+    } else {
+        src
+    };
+    if args.keep_unminimized {
+        let path = args.out_dir.join(format!("{}.unminimized.rs", bin.name));
+        std::fs::write(path, &src)?;
+    }
+    let (src, stats) = minimize_code_with_stats(src, options)?;
+    eprintln!(
+        "Bundled {} with {} rustc runs in {:.2?} ({:.2?} in rustc)",
+        bin.name, stats.rustc_runs, stats.total_time, stats.rustc_time
+    );
+    let src = format!("// Bundled by cobu (https://github.com/skrobchik/cobu)\n{src}");
+    let path = bundle_path(&args.out_dir, bin);
+    check_bundle(&path, &src, &options.edition, &sources)?;
+    if let Some(rust_version) = &args.rust_version {
+        check_rust_version(
+            &path,
+            &src,
+            &options.edition,
+            rust_version,
+            &sources,
+            args.rerun_if_changed,
+        )?;
+    }
+    if let Some(size_limit) = args.size_limit {
+        check_size_limit(&src, size_limit)?;
+    }
+    let mut f = std::fs::File::create(path)?;
+    f.write_all(src.as_bytes())?;
+    Ok(sources)
+}
+
+/// Entry point for the `build.rs` of a contest package. Bundles every binary of the package
+/// into its `dist` directory and tells cargo to only run the script again when a file that
+/// feeds the bundles changes.
+pub fn build_script() -> anyhow::Result<()> {
+    let manifest_dir = PathBuf::from(
+        std::env::var("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR is not set")?,
+    );
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    // Dependencies and new binaries change which files feed the bundles.
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
+    println!(
+        "cargo:rerun-if-changed={}",
+        manifest_dir.join("src").join("bin").display()
+    );
+
+    cli(Args {
+        manifest_path: Some(manifest_path),
+        out_dir: manifest_dir.join("dist"),
+        rerun_if_changed: true,
+        ..Default::default()
+    })
+}
+
 pub fn parse_cli(concat_args: Option<&[String]>) -> anyhow::Result<()> {
     let mut args: Vec<String> = std::env::args().collect();
     if let Some(concat_args) = concat_args {
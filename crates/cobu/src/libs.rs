@@ -0,0 +1,143 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use cargo_metadata::{DependencyKind, Metadata, Package, PackageId, Target};
+
+/// A library crate to be inlined into bundles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lib {
+    /// Root source file of the library, usually `src/lib.rs`.
+    pub src_path: PathBuf,
+    /// Enabled features, used to resolve `#[cfg(feature = "...")]` inside the library.
+    pub features: BTreeSet<String>,
+}
+
+impl Lib {
+    pub fn new(src_path: PathBuf) -> Self {
+        Self {
+            src_path,
+            features: BTreeSet::new(),
+        }
+    }
+}
+
+fn lib_target(package: &Package) -> Option<&Target> {
+    package.targets.iter().find(|t| t.is_lib() || t.is_rlib())
+}
+
+/// Finds the sources of a registry package inside a `cargo vendor` directory, which names
+/// packages either `name` or `name-version`.
+fn vendored_src_path(
+    vendor_dir: &Path,
+    package: &Package,
+    lib: &Target,
+) -> anyhow::Result<PathBuf> {
+    let package_dir = package
+        .manifest_path
+        .parent()
+        .context("Manifest path has no parent directory")?;
+    let relative_src_path = lib.src_path.strip_prefix(package_dir)?;
+    [
+        vendor_dir.join(format!("{}-{}", package.name, package.version)),
+        vendor_dir.join(package.name.as_str()),
+    ]
+    .into_iter()
+    .find(|dir| dir.join("Cargo.toml").is_file())
+    .map(|dir| dir.join(relative_src_path))
+    .with_context(|| {
+        format!(
+            "Package {} {} not found in vendor directory {}",
+            package.name,
+            package.version,
+            vendor_dir.display()
+        )
+    })
+}
+
+/// Finds the library targets of the path dependencies of `package`, transitively.
+///
+/// Registry dependencies are only bundled when their package or library name is in
+/// `registry_libs`, together with everything they depend on. Their sources are taken from
+/// `vendor_dir` when given, and otherwise from wherever cargo downloaded them to.
+pub fn discover_libs(
+    metadata: &Metadata,
+    package: &Package,
+    registry_libs: &[String],
+    vendor_dir: Option<&Path>,
+) -> anyhow::Result<BTreeMap<String, Lib>> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .context("Dependency resolution missing from cargo metadata")?;
+    let is_selected = |package: &Package, lib: &Target| {
+        registry_libs.iter().any(|selected| {
+            let selected = selected.replace('-', "_");
+            selected == package.name.replace('-', "_") || selected == lib.name.replace('-', "_")
+        })
+    };
+
+    let mut libs: BTreeMap<String, Lib> = BTreeMap::new();
+    let mut visited: BTreeSet<&PackageId> = BTreeSet::new();
+    // Packages whose dependencies get bundled, and whether they are registry libraries,
+    // in which case all of their dependencies are needed for them to compile.
+    let mut queue: Vec<(&PackageId, bool)> = vec![(&package.id, false)];
+    while let Some((id, is_registry_lib)) = queue.pop() {
+        for dep in &resolve[id].deps {
+            if !dep
+                .dep_kinds
+                .iter()
+                .any(|k| k.kind == DependencyKind::Normal)
+            {
+                continue;
+            }
+            let dep_package = &metadata[&dep.pkg];
+            let Some(lib) = lib_target(dep_package) else {
+                continue;
+            };
+            let is_registry = dep_package.source.is_some();
+            if is_registry && !is_registry_lib && !is_selected(dep_package, lib) {
+                continue;
+            }
+
+            let src_path = match vendor_dir {
+                Some(vendor_dir) if is_registry => vendored_src_path(vendor_dir, dep_package, lib)?,
+                _ => PathBuf::from(lib.src_path.clone()),
+            };
+            if !src_path.is_file() {
+                anyhow::bail!(
+                    "Source of {} {} not found at {}, try running `cargo fetch`",
+                    dep_package.name,
+                    dep_package.version,
+                    src_path.display()
+                );
+            }
+            let features = resolve[&dep.pkg]
+                .features
+                .iter()
+                .map(|f| f.to_string())
+                .collect();
+            // Code that depends on the library refers to it by its (possibly renamed) crate name.
+            let name = dep.name.replace('-', "_");
+            let lib = Lib { src_path, features };
+            match libs.get(&name) {
+                Some(existing) if existing.src_path == lib.src_path => (),
+                Some(existing) => anyhow::bail!(
+                    "Libraries {} and {} are both named `{name}`",
+                    existing.src_path.display(),
+                    lib.src_path.display()
+                ),
+                None => {
+                    libs.insert(name, lib);
+                }
+            }
+            if visited.insert(&dep.pkg) {
+                queue.push((&dep.pkg, is_registry));
+            }
+        }
+    }
+    Ok(libs)
+}
@@ -0,0 +1,563 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+use syn::{
+    parse::{ParseStream, Parser},
+    spanned::Spanned,
+    visit::Visit,
+};
+
+use crate::{expand::replace_ranges, scope::ItemPath};
+
+/// Expansions of expansions are almost certainly a macro invoking itself forever.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// A token of a `macro_rules!` matcher or transcriber.
+#[derive(Debug)]
+enum MacroToken {
+    Token(TokenTree),
+    Group(Delimiter, Vec<MacroToken>),
+    /// `$name:fragment` in a matcher, or `$name` in a transcriber
+    Var(String, Option<String>),
+    Crate,
+    /// `$(...) sep op`
+    Repeat(Vec<MacroToken>, Option<TokenTree>, char),
+}
+
+fn parse_macro_tokens(tokens: TokenStream, is_matcher: bool) -> Option<Vec<MacroToken>> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut output = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == '$' => match tokens.next()? {
+                TokenTree::Ident(ident) if ident == "crate" => output.push(MacroToken::Crate),
+                TokenTree::Ident(ident) => {
+                    let fragment = if is_matcher {
+                        match (tokens.next()?, tokens.next()?) {
+                            (TokenTree::Punct(p), TokenTree::Ident(fragment))
+                                if p.as_char() == ':' =>
+                            {
+                                Some(fragment.to_string())
+                            }
+                            _ => return None,
+                        }
+                    } else {
+                        None
+                    };
+                    output.push(MacroToken::Var(ident.to_string(), fragment));
+                }
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                    let inner = parse_macro_tokens(group.stream(), is_matcher)?;
+                    let (separator, op) = match tokens.next()? {
+                        TokenTree::Punct(p) if matches!(p.as_char(), '*' | '+' | '?') => {
+                            (None, p.as_char())
+                        }
+                        separator => match tokens.next()? {
+                            TokenTree::Punct(p) if matches!(p.as_char(), '*' | '+' | '?') => {
+                                (Some(separator), p.as_char())
+                            }
+                            _ => return None,
+                        },
+                    };
+                    output.push(MacroToken::Repeat(inner, separator, op));
+                }
+                _ => return None,
+            },
+            TokenTree::Group(group) => output.push(MacroToken::Group(
+                group.delimiter(),
+                parse_macro_tokens(group.stream(), is_matcher)?,
+            )),
+            token => output.push(MacroToken::Token(token)),
+        }
+    }
+    Some(output)
+}
+
+struct MacroRule {
+    matcher: Vec<MacroToken>,
+    transcriber: Vec<MacroToken>,
+}
+
+/// Parses the rules of a `macro_rules!` body, like `(matcher) => { transcriber };`.
+fn parse_macro_rules(body: TokenStream) -> Option<Vec<MacroRule>> {
+    let mut tokens = body.into_iter();
+    let mut rules = Vec::new();
+    while let Some(matcher) = tokens.next() {
+        let (TokenTree::Group(matcher), TokenTree::Punct(eq), TokenTree::Punct(gt)) =
+            (matcher, tokens.next()?, tokens.next()?)
+        else {
+            return None;
+        };
+        let TokenTree::Group(transcriber) = tokens.next()? else {
+            return None;
+        };
+        if eq.as_char() != '=' || gt.as_char() != '>' {
+            return None;
+        }
+        rules.push(MacroRule {
+            matcher: parse_macro_tokens(matcher.stream(), true)?,
+            transcriber: parse_macro_tokens(transcriber.stream(), false)?,
+        });
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+            None => break,
+            _ => return None,
+        }
+    }
+    Some(rules)
+}
+
+/// What a metavariable matched.
+enum Fragment {
+    Tokens(Vec<TokenTree>, String),
+    Repeated(Vec<Fragment>),
+}
+
+type Bindings = BTreeMap<String, Fragment>;
+
+fn tokens_eq(a: &TokenTree, b: &TokenTree) -> bool {
+    match (a, b) {
+        (TokenTree::Ident(a), TokenTree::Ident(b)) => a == b,
+        (TokenTree::Punct(a), TokenTree::Punct(b)) => a.as_char() == b.as_char(),
+        (TokenTree::Literal(a), TokenTree::Literal(b)) => a.to_string() == b.to_string(),
+        _ => false,
+    }
+}
+
+/// Number of tokens at the start of `input` that syn parses with `parser`.
+fn parsed_len(input: &[TokenTree], parser: fn(ParseStream) -> syn::Result<()>) -> Option<usize> {
+    let tokens: TokenStream = input.iter().cloned().collect();
+    let rest = (|stream: ParseStream| {
+        parser(stream)?;
+        stream.parse::<TokenStream>()
+    })
+    .parse2(tokens)
+    .ok()?;
+    Some(input.len() - rest.into_iter().count())
+}
+
+/// Number of tokens at the start of `input` matched by a fragment specifier like `ty`.
+fn fragment_len(fragment: &str, input: &[TokenTree]) -> Option<usize> {
+    let len = match fragment {
+        "tt" => 1,
+        "ident" => match input.first()? {
+            TokenTree::Ident(ident) if ident != "_" => 1,
+            _ => return None,
+        },
+        "lifetime" => match (input.first()?, input.get(1)?) {
+            (TokenTree::Punct(p), TokenTree::Ident(_)) if p.as_char() == '\'' => 2,
+            _ => return None,
+        },
+        "literal" => match (input.first()?, input.get(1)) {
+            (TokenTree::Literal(_), _) => 1,
+            (TokenTree::Ident(ident), _) if ident == "true" || ident == "false" => 1,
+            (TokenTree::Punct(p), Some(TokenTree::Literal(_))) if p.as_char() == '-' => 2,
+            _ => return None,
+        },
+        // An empty visibility matches too
+        "vis" => return parsed_len(input, |s| s.parse::<syn::Visibility>().map(drop)),
+        "block" => parsed_len(input, |s| s.parse::<syn::Block>().map(drop))?,
+        "expr" => parsed_len(input, |s| s.parse::<syn::Expr>().map(drop))?,
+        "item" => parsed_len(input, |s| s.parse::<syn::Item>().map(drop))?,
+        "meta" => parsed_len(input, |s| s.parse::<syn::Meta>().map(drop))?,
+        "pat" => parsed_len(input, |s| {
+            syn::Pat::parse_multi_with_leading_vert(s).map(drop)
+        })?,
+        "pat_param" => parsed_len(input, |s| syn::Pat::parse_single(s).map(drop))?,
+        "path" => parsed_len(input, |s| s.parse::<syn::Path>().map(drop))?,
+        "stmt" => parsed_len(input, |s| s.parse::<syn::Stmt>().map(drop))?,
+        "ty" => parsed_len(input, |s| s.parse::<syn::Type>().map(drop))?,
+        _ => return None,
+    };
+    (len > 0 && len <= input.len()).then_some(len)
+}
+
+fn collect_vars(tokens: &[MacroToken], vars: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            MacroToken::Var(name, _) => {
+                vars.insert(name.clone());
+            }
+            MacroToken::Group(_, inner) | MacroToken::Repeat(inner, _, _) => {
+                collect_vars(inner, vars)
+            }
+            MacroToken::Token(_) | MacroToken::Crate => (),
+        }
+    }
+}
+
+/// Matches `input` from `pos` against a matcher, returning where the match ends. Repetitions
+/// are matched greedily, which is enough for the matchers rustc accepts in practice.
+fn match_tokens(
+    matcher: &[MacroToken],
+    input: &[TokenTree],
+    mut pos: usize,
+    bindings: &mut Bindings,
+) -> Option<usize> {
+    for token in matcher {
+        match token {
+            MacroToken::Token(expected) => {
+                if !tokens_eq(expected, input.get(pos)?) {
+                    return None;
+                }
+                pos += 1;
+            }
+            MacroToken::Group(delimiter, inner) => {
+                let TokenTree::Group(group) = input.get(pos)? else {
+                    return None;
+                };
+                let group_input: Vec<TokenTree> = group.stream().into_iter().collect();
+                if group.delimiter() != *delimiter
+                    || match_tokens(inner, &group_input, 0, bindings)? != group_input.len()
+                {
+                    return None;
+                }
+                pos += 1;
+            }
+            MacroToken::Var(name, fragment) => {
+                let fragment = fragment.as_ref()?;
+                let len = fragment_len(fragment, &input[pos.min(input.len())..])?;
+                let tokens = input[pos..pos + len].to_vec();
+                bindings.insert(name.clone(), Fragment::Tokens(tokens, fragment.clone()));
+                pos += len;
+            }
+            MacroToken::Crate => return None,
+            MacroToken::Repeat(inner, separator, op) => {
+                let mut iterations: Vec<Bindings> = Vec::new();
+                loop {
+                    let mut next = pos;
+                    if let (false, Some(separator)) = (iterations.is_empty(), separator) {
+                        match input.get(next) {
+                            Some(token) if tokens_eq(separator, token) => next += 1,
+                            _ => break,
+                        }
+                    }
+                    let mut iteration = Bindings::new();
+                    match match_tokens(inner, input, next, &mut iteration) {
+                        Some(end) if end > pos => {
+                            iterations.push(iteration);
+                            pos = end;
+                        }
+                        _ => break,
+                    }
+                    if *op == '?' {
+                        break;
+                    }
+                }
+                if *op == '+' && iterations.is_empty() {
+                    return None;
+                }
+                let mut vars = BTreeSet::new();
+                collect_vars(inner, &mut vars);
+                for var in vars {
+                    let fragments = iterations
+                        .iter_mut()
+                        .map(|iteration| {
+                            iteration
+                                .remove(&var)
+                                .unwrap_or(Fragment::Repeated(Vec::new()))
+                        })
+                        .collect();
+                    bindings.insert(var, Fragment::Repeated(fragments));
+                }
+            }
+        }
+    }
+    Some(pos)
+}
+
+/// Looks up a metavariable inside the repetitions at `indices`.
+fn lookup<'a>(bindings: &'a Bindings, name: &str, indices: &[usize]) -> Option<&'a Fragment> {
+    let mut fragment = bindings.get(name)?;
+    for &i in indices {
+        match fragment {
+            Fragment::Repeated(fragments) => fragment = fragments.get(i)?,
+            Fragment::Tokens(..) => break,
+        }
+    }
+    Some(fragment)
+}
+
+/// Whether an expression between these tokens stands on its own, like in `let x = $e;`, so it
+/// doesn't need parentheses.
+fn is_expr_boundary(before: &[TokenTree], after: Option<&MacroToken>) -> bool {
+    let is_punct = |token: Option<&TokenTree>, chars: &[char]| matches!(token, Some(TokenTree::Punct(p)) if chars.contains(&p.as_char()));
+    let before_ok = match before {
+        [] => true,
+        [.., last] if is_punct(Some(last), &[',', ';']) => true,
+        // `=` but not `==` or `<=`
+        [.., second_last, last] => {
+            is_punct(Some(last), &['=']) && !matches!(second_last, TokenTree::Punct(_))
+        }
+        [last] => is_punct(Some(last), &['=']),
+    };
+    let after_ok = match after {
+        None => true,
+        Some(MacroToken::Token(token)) => is_punct(Some(token), &[',', ';']),
+        Some(_) => false,
+    };
+    before_ok && after_ok
+}
+
+fn transcribe(
+    transcriber: &[MacroToken],
+    bindings: &Bindings,
+    indices: &mut Vec<usize>,
+    output: &mut Vec<TokenTree>,
+) -> Option<()> {
+    for (i, token) in transcriber.iter().enumerate() {
+        match token {
+            MacroToken::Token(token) => output.push(token.clone()),
+            MacroToken::Group(delimiter, inner) => {
+                let mut group_output = Vec::new();
+                transcribe(inner, bindings, indices, &mut group_output)?;
+                let group = Group::new(*delimiter, group_output.into_iter().collect());
+                output.push(TokenTree::Group(group));
+            }
+            MacroToken::Crate => {
+                output.push(TokenTree::Ident(Ident::new("crate", Span::call_site())))
+            }
+            MacroToken::Var(name, _) => match lookup(bindings, name, indices)? {
+                // Keep `$e * 2` with `$e` as `1 + 1` meaning `(1 + 1) * 2`
+                Fragment::Tokens(tokens, fragment)
+                    if fragment == "expr"
+                        && tokens.len() > 1
+                        && !is_expr_boundary(output, transcriber.get(i + 1)) =>
+                {
+                    let group =
+                        Group::new(Delimiter::Parenthesis, tokens.iter().cloned().collect());
+                    output.push(TokenTree::Group(group));
+                }
+                Fragment::Tokens(tokens, _) => output.extend(tokens.iter().cloned()),
+                Fragment::Repeated(_) => return None,
+            },
+            MacroToken::Repeat(inner, separator, _) => {
+                let mut vars = BTreeSet::new();
+                collect_vars(inner, &mut vars);
+                let mut count = None;
+                for var in &vars {
+                    if let Some(Fragment::Repeated(fragments)) = lookup(bindings, var, indices) {
+                        if count.is_some_and(|count| count != fragments.len()) {
+                            return None;
+                        }
+                        count = Some(fragments.len());
+                    }
+                }
+                for i in 0..count? {
+                    if let (true, Some(separator)) = (i > 0, separator) {
+                        output.push(separator.clone());
+                    }
+                    indices.push(i);
+                    transcribe(inner, bindings, indices, output)?;
+                    indices.pop();
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+/// Expands an invocation with the first rule that matches it, as long as the expansion parses
+/// as items.
+fn expand(rules: &[MacroRule], input: TokenStream) -> Option<TokenStream> {
+    let input: Vec<TokenTree> = input.into_iter().collect();
+    let rule = rules.iter().find_map(|rule| {
+        let mut bindings = Bindings::new();
+        let end = match_tokens(&rule.matcher, &input, 0, &mut bindings)?;
+        (end == input.len()).then_some((rule, bindings))
+    });
+    let (rule, bindings) = rule?;
+    let mut output = Vec::new();
+    transcribe(&rule.transcriber, &bindings, &mut Vec::new(), &mut output)?;
+    let output: TokenStream = output.into_iter().collect();
+    syn::parse2::<syn::File>(output.clone()).ok()?;
+    Some(output)
+}
+
+/// A `macro_rules!` definition or invocation in item position.
+struct MacroItem<'ast> {
+    module: ItemPath,
+    item: &'ast syn::ItemMacro,
+}
+
+#[derive(Default)]
+struct MacroItems<'ast> {
+    definitions: BTreeMap<String, Vec<MacroItem<'ast>>>,
+    invocations: Vec<MacroItem<'ast>>,
+}
+
+impl<'ast> MacroItems<'ast> {
+    /// Only looks at the items of modules: macros in function bodies are scoped to them.
+    fn collect(&mut self, items: &'ast [syn::Item], module: &mut ItemPath) {
+        for item in items {
+            match item {
+                syn::Item::Macro(item) => {
+                    let macro_item = MacroItem {
+                        module: module.clone(),
+                        item,
+                    };
+                    match &item.ident {
+                        Some(ident) if item.mac.path.is_ident("macro_rules") => self
+                            .definitions
+                            .entry(ident.to_string())
+                            .or_default()
+                            .push(macro_item),
+                        Some(_) => (),
+                        None => self.invocations.push(macro_item),
+                    }
+                }
+                syn::Item::Mod(item) => {
+                    if let Some((_, items)) = &item.content {
+                        module.push(item.ident.to_string());
+                        self.collect(items, module);
+                        module.pop();
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// The definition a local invocation refers to: the last one before it, in its module or
+    /// one of the modules around it.
+    fn definition(&self, invocation: &MacroItem) -> Option<&MacroItem<'ast>> {
+        let name = invocation.item.mac.path.get_ident()?.to_string();
+        let start = invocation.item.span().byte_range().start;
+        self.definitions
+            .get(&name)?
+            .iter()
+            .rev()
+            .find(|definition| {
+                invocation.module.starts_with(&definition.module)
+                    && definition.item.span().byte_range().end <= start
+            })
+    }
+}
+
+/// Finds the names of all macros invoked anywhere, including inside other macros.
+#[derive(Default)]
+struct MacroNameVisitor {
+    output_names: BTreeSet<String>,
+    /// Names given by `use ... as ...`, with the names they stand for
+    output_renames: Vec<(String, String)>,
+}
+
+impl MacroNameVisitor {
+    fn visit_tokens(&mut self, tokens: TokenStream) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.visit_tokens(group.stream()),
+                TokenTree::Ident(ident) => {
+                    if matches!(tokens.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '!')
+                    {
+                        self.output_names.insert(ident.to_string());
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for MacroNameVisitor {
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        if let Some(ident) = i.path.segments.last() {
+            self.output_names.insert(ident.ident.to_string());
+        }
+        self.visit_tokens(i.tokens.clone());
+        syn::visit::visit_macro(self, i);
+    }
+
+    // A macro invoking itself recursively doesn't keep it alive.
+    fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+        match &i.ident {
+            Some(ident) if i.mac.path.is_ident("macro_rules") => {
+                let mut visitor = MacroNameVisitor::default();
+                visitor.visit_tokens(i.mac.tokens.clone());
+                visitor.output_names.remove(&ident.to_string());
+                self.output_names.extend(visitor.output_names);
+            }
+            _ => syn::visit::visit_item_macro(self, i),
+        }
+    }
+
+    fn visit_use_rename(&mut self, i: &'ast syn::UseRename) {
+        self.output_renames
+            .push((i.rename.to_string(), i.ident.to_string()));
+    }
+}
+
+/// Names of the macros invoked anywhere in a file, including inside other macros, whether by
+/// a plain name like `name!`, a path like `crate::lib::name!` or a name it was imported as
+/// with `use crate::lib::name as other;`.
+pub(crate) fn invoked_macro_names(file: &syn::File) -> BTreeSet<String> {
+    let mut visitor = MacroNameVisitor::default();
+    visitor.visit_file(file);
+    let mut names = visitor.output_names;
+    // A rename can be renamed again.
+    loop {
+        let original_names: Vec<String> = visitor
+            .output_renames
+            .iter()
+            .filter(|(rename, original)| names.contains(rename) && !names.contains(original))
+            .map(|(_, original)| original.clone())
+            .collect();
+        if original_names.is_empty() {
+            return names;
+        }
+        names.extend(original_names);
+    }
+}
+
+/// Expands local `macro_rules!` invocations in item position, like `one_impl!(u32);`, so each
+/// generated item can be removed on its own, then removes the definitions of the expanded
+/// macros that are no longer invoked anywhere. Invocations that can't be expanded are left
+/// as they are.
+pub fn expand_macro_rules(mut src: String) -> anyhow::Result<String> {
+    let mut expanded_macros = BTreeSet::new();
+    for _ in 0..MAX_EXPANSION_DEPTH {
+        let ast = syn::parse_file(&src)?;
+        let mut macro_items = MacroItems::default();
+        macro_items.collect(&ast.items, &mut Vec::new());
+        let mut edits = Vec::new();
+        for invocation in &macro_items.invocations {
+            if !invocation.item.attrs.is_empty() {
+                continue;
+            }
+            let Some(definition) = macro_items.definition(invocation) else {
+                continue;
+            };
+            let Some(rules) = parse_macro_rules(definition.item.mac.tokens.clone()) else {
+                continue;
+            };
+            if let Some(expansion) = expand(&rules, invocation.item.mac.tokens.clone()) {
+                edits.push((invocation.item.span().byte_range(), expansion.to_string()));
+                expanded_macros.insert(invocation.item.mac.path.get_ident().unwrap().to_string());
+            }
+        }
+        if edits.is_empty() {
+            break;
+        }
+        src = replace_ranges(&src, edits);
+    }
+
+    let ast = syn::parse_file(&src)?;
+    let mut macro_items = MacroItems::default();
+    macro_items.collect(&ast.items, &mut Vec::new());
+    let invoked_macros = invoked_macro_names(&ast);
+    let edits = expanded_macros
+        .iter()
+        .filter(|name| !invoked_macros.contains(*name))
+        .flat_map(|name| &macro_items.definitions[name])
+        .filter(|definition| {
+            !definition
+                .item
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("macro_export"))
+        })
+        .map(|definition| (definition.item.span().byte_range(), String::new()))
+        .collect();
+    Ok(replace_ranges(&src, edits))
+}
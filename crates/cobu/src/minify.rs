@@ -0,0 +1,113 @@
+use std::ops::Range;
+
+use quote::ToTokens;
+use syn::{spanned::Spanned, visit::Visit};
+
+use crate::expand::replace_ranges;
+
+/// Whether an attribute with this path doesn't change what the code does, like docs and lint
+/// or optimization hints.
+pub(crate) fn is_cosmetic_attribute_path(path: &[String]) -> bool {
+    match path {
+        [first, ..] if first == "rustfmt" => true,
+        [name] => ["doc", "must_use", "inline", "cold"].contains(&name.as_str()),
+        _ => false,
+    }
+}
+
+fn is_cosmetic_attribute(attribute: &syn::Attribute) -> bool {
+    let path: Vec<String> = attribute
+        .path()
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    is_cosmetic_attribute_path(&path)
+}
+
+#[derive(Default)]
+struct CosmeticAttributeVisitor {
+    output_ranges: Vec<Range<usize>>,
+}
+
+impl<'ast> Visit<'ast> for CosmeticAttributeVisitor {
+    fn visit_attribute(&mut self, i: &'ast syn::Attribute) {
+        if is_cosmetic_attribute(i) {
+            self.output_ranges.push(i.span().byte_range());
+        }
+    }
+}
+
+/// Removes docs, comments and cosmetic attributes, and prints each top level item on a single
+/// line.
+pub fn minify(src: String) -> anyhow::Result<String> {
+    let ast = syn::parse_file(&src)?;
+    let mut visitor = CosmeticAttributeVisitor::default();
+    visitor.visit_file(&ast);
+    let edits = visitor
+        .output_ranges
+        .into_iter()
+        .map(|range| (range, String::new()))
+        .collect();
+    let src = replace_ranges(&src, edits);
+
+    // Comments aren't tokens, so printing the tokens back drops them.
+    let ast = syn::parse_file(&src)?;
+    let lines: Vec<String> = ast
+        .attrs
+        .iter()
+        .map(|a| a.to_token_stream().to_string())
+        .chain(ast.items.iter().map(|i| i.to_token_stream().to_string()))
+        .collect();
+    Ok(lines.join("\n") + "\n")
+}
+
+struct ModuleSize {
+    path: String,
+    depth: usize,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct ModuleSizeVisitor {
+    module: Vec<String>,
+    output_sizes: Vec<ModuleSize>,
+}
+
+impl<'ast> Visit<'ast> for ModuleSizeVisitor {
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        self.output_sizes.push(ModuleSize {
+            path: format!("crate::{}", self.module.join("::")),
+            depth: self.module.len(),
+            bytes: i.span().byte_range().len(),
+        });
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+
+    // Items in functions aren't worth listing.
+    fn visit_block(&mut self, _: &'ast syn::Block) {}
+}
+
+/// Fails if `src` is over `size_limit` bytes, listing how much of it each module takes.
+pub(crate) fn check_size_limit(src: &str, size_limit: usize) -> anyhow::Result<()> {
+    if src.len() <= size_limit {
+        return Ok(());
+    }
+    let mut visitor = ModuleSizeVisitor::default();
+    visitor.visit_file(&syn::parse_file(src)?);
+    let mut breakdown = format!("{:>10}  crate", src.len());
+    for size in visitor.output_sizes {
+        breakdown.push_str(&format!(
+            "\n{:>10}  {}{}",
+            size.bytes,
+            "  ".repeat(size.depth),
+            size.path
+        ));
+    }
+    anyhow::bail!(
+        "Bundle is {} bytes, over the size limit of {size_limit} bytes. Bytes per module:\n{breakdown}",
+        src.len()
+    )
+}
@@ -0,0 +1,573 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use syn::{spanned::Spanned, visit::Visit};
+
+use crate::{
+    dead_code::PathVisitor,
+    scope::{ItemPath, Scopes},
+};
+
+/// Paths written in a piece of code, along with the module they are written in.
+type ModulePaths = Vec<(ItemPath, Vec<String>)>;
+
+/// Splits tokens into the paths they contain, like `a::b::c` or `$crate::a`, and collects all
+/// their identifiers.
+fn token_paths(tokens: TokenStream, paths: &mut Vec<Vec<String>>, names: &mut BTreeSet<String>) {
+    let mut current: Vec<String> = Vec::new();
+    let mut colons = 0;
+    let mut dollar = false;
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                let ident = ident.to_string();
+                names.insert(ident.clone());
+                if colons != 2 && !current.is_empty() {
+                    paths.push(std::mem::take(&mut current));
+                }
+                if dollar && ident != "crate" {
+                    // A metavariable
+                    current.clear();
+                } else {
+                    current.push(ident);
+                }
+                colons = 0;
+                dollar = false;
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ':' && colons < 2 => colons += 1,
+            TokenTree::Punct(punct) => {
+                dollar = punct.as_char() == '$';
+                colons = 0;
+                if !current.is_empty() {
+                    paths.push(std::mem::take(&mut current));
+                }
+            }
+            TokenTree::Group(group) => {
+                colons = 0;
+                dollar = false;
+                if !current.is_empty() {
+                    paths.push(std::mem::take(&mut current));
+                }
+                token_paths(group.stream(), paths, names);
+            }
+            TokenTree::Literal(_) => {
+                colons = 0;
+                dollar = false;
+                if !current.is_empty() {
+                    paths.push(std::mem::take(&mut current));
+                }
+            }
+        }
+    }
+    if !current.is_empty() {
+        paths.push(current);
+    }
+}
+
+/// The traits implemented by the items a `macro_rules!` expands to, if it expands to nothing
+/// but trait impls, like `impl One for $t { ... }`.
+fn macro_impl_traits(macro_body: TokenStream) -> Option<Vec<Vec<String>>> {
+    let mut traits = Vec::new();
+    let mut tokens = macro_body.into_iter();
+    // Rules look like `(matcher) => { transcriber };`
+    while let Some(token) = tokens.next() {
+        if let TokenTree::Punct(punct) = &token {
+            if punct.as_char() == '>' {
+                if let Some(TokenTree::Group(transcriber)) = tokens.next() {
+                    transcriber_impl_traits(transcriber.stream(), &mut traits)?;
+                }
+            }
+        }
+    }
+    (!traits.is_empty()).then_some(traits)
+}
+
+fn transcriber_impl_traits(tokens: TokenStream, traits: &mut Vec<Vec<String>>) -> Option<()> {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            TokenTree::Ident(ident) if ident == "impl" => {
+                i += 1;
+                // Skip the generics of the impl
+                if matches!(&tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == '<') {
+                    let mut depth = 0;
+                    while let Some(token) = tokens.get(i) {
+                        if let TokenTree::Punct(p) = token {
+                            match p.as_char() {
+                                '<' => depth += 1,
+                                '>' => depth -= 1,
+                                _ => (),
+                            }
+                        }
+                        i += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                let mut trait_path = Vec::new();
+                loop {
+                    match tokens.get(i)? {
+                        TokenTree::Ident(ident) if ident == "for" => break,
+                        TokenTree::Ident(ident) => trait_path.push(ident.to_string()),
+                        TokenTree::Punct(p) if p.as_char() == ':' => (),
+                        TokenTree::Punct(p) if p.as_char() == '$' => match tokens.get(i + 1)? {
+                            TokenTree::Ident(ident) if ident == "crate" => (),
+                            // The trait is a metavariable
+                            _ => return None,
+                        },
+                        // Generic arguments of the trait
+                        TokenTree::Punct(p) if p.as_char() == '<' => {
+                            while !matches!(tokens.get(i)?, TokenTree::Ident(ident) if ident == "for")
+                            {
+                                i += 1;
+                            }
+                            break;
+                        }
+                        // An inherent impl, or something else entirely
+                        _ => return None,
+                    }
+                    i += 1;
+                }
+                traits.push(trait_path);
+                // The self type and the body of the impl
+                while !matches!(&tokens.get(i)?, TokenTree::Group(g) if g.delimiter() == Delimiter::Brace)
+                {
+                    i += 1;
+                }
+            }
+            // A repetition like `$(impl One for $t {})*`
+            TokenTree::Group(group)
+                if i > 0 && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '$') =>
+            {
+                transcriber_impl_traits(group.stream(), traits)?;
+            }
+            TokenTree::Punct(_) | TokenTree::Literal(_) => (),
+            TokenTree::Ident(ident)
+                if ident.to_string().starts_with(char::is_lowercase) && ident != "impl" =>
+            {
+                // Any other item, or a metavariable in item position
+                if i == 0 || !matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '$') {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(())
+}
+
+/// Collects the paths and names referenced by a piece of code. Items nested in it are
+/// indexed on their own, so they are skipped.
+struct ReferenceVisitor<'a> {
+    module: ItemPath,
+    paths: &'a mut ModulePaths,
+    names: &'a mut BTreeSet<String>,
+}
+
+impl<'ast> Visit<'ast> for ReferenceVisitor<'_> {
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+
+    fn visit_path(&mut self, i: &'ast syn::Path) {
+        let segments: Vec<String> = i.segments.iter().map(|s| s.ident.to_string()).collect();
+        if let Some(last) = segments.last() {
+            self.names.insert(last.clone());
+        }
+        if i.leading_colon.is_none() {
+            self.paths.push((self.module.clone(), segments));
+        }
+        syn::visit::visit_path(self, i);
+    }
+
+    fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+        self.names.insert(i.method.to_string());
+        syn::visit::visit_expr_method_call(self, i);
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        self.visit_path(&i.path);
+        let mut paths = Vec::new();
+        token_paths(i.tokens.clone(), &mut paths, self.names);
+        self.paths
+            .extend(paths.into_iter().map(|path| (self.module.clone(), path)));
+    }
+}
+
+/// Every item of a file by path, and the impls and macro invocations in item position.
+#[derive(Default)]
+struct ItemIndex<'ast> {
+    module: ItemPath,
+    items: BTreeMap<ItemPath, Vec<&'ast syn::Item>>,
+    impls: Vec<(ItemPath, &'ast syn::ItemImpl)>,
+    /// `macro_rules!` definitions by name
+    macros: BTreeMap<String, Vec<(ItemPath, &'ast syn::ItemMacro)>>,
+    invocations: Vec<(ItemPath, &'ast syn::ItemMacro)>,
+}
+
+impl ItemIndex<'_> {
+    /// Local traits with the names of their items.
+    fn traits(&self) -> BTreeMap<ItemPath, BTreeSet<String>> {
+        self.items
+            .iter()
+            .flat_map(|(path, items)| {
+                items.iter().filter_map(move |item| match item {
+                    syn::Item::Trait(item) => Some((path.clone(), trait_item_names(item))),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'ast> Visit<'ast> for ItemIndex<'ast> {
+    fn visit_item(&mut self, i: &'ast syn::Item) {
+        let ident = match i {
+            syn::Item::Const(item) => Some(&item.ident),
+            syn::Item::Enum(item) => Some(&item.ident),
+            syn::Item::Fn(item) => Some(&item.sig.ident),
+            syn::Item::Static(item) => Some(&item.ident),
+            syn::Item::Struct(item) => Some(&item.ident),
+            syn::Item::Trait(item) => Some(&item.ident),
+            syn::Item::TraitAlias(item) => Some(&item.ident),
+            syn::Item::Type(item) => Some(&item.ident),
+            syn::Item::Union(item) => Some(&item.ident),
+            _ => None,
+        };
+        if let Some(ident) = ident {
+            let mut path = self.module.clone();
+            path.push(ident.to_string());
+            self.items.entry(path).or_default().push(i);
+        }
+        match i {
+            syn::Item::Impl(item) => self.impls.push((self.module.clone(), item)),
+            syn::Item::Macro(item) => match &item.ident {
+                Some(ident) if item.mac.path.is_ident("macro_rules") => self
+                    .macros
+                    .entry(ident.to_string())
+                    .or_default()
+                    .push((self.module.clone(), item)),
+                Some(_) => (),
+                None => self.invocations.push((self.module.clone(), item)),
+            },
+            _ => (),
+        }
+        syn::visit::visit_item(self, i);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+}
+
+/// Names of the items of a trait, which calls like `x.one()` or `T::one()` might refer to.
+fn trait_item_names(item: &syn::ItemTrait) -> BTreeSet<String> {
+    item.items
+        .iter()
+        .filter_map(|item| match item {
+            syn::TraitItem::Const(item) => Some(item.ident.to_string()),
+            syn::TraitItem::Fn(item) => Some(item.sig.ident.to_string()),
+            syn::TraitItem::Type(item) => Some(item.ident.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Walks the items reachable from `main`, through the paths and macros they mention, the
+/// impls of the types and traits they use, and the traits whose methods they call.
+struct Reachability<'ast> {
+    scopes: Scopes,
+    index: ItemIndex<'ast>,
+    /// Local traits with the names of their items
+    traits: BTreeMap<ItemPath, BTreeSet<String>>,
+    /// Local traits implemented by each macro invocation, or `None` when it expands to
+    /// anything else, or its traits can't be resolved
+    invocation_traits: Vec<Option<Vec<ItemPath>>>,
+    reachable_items: BTreeSet<ItemPath>,
+    reachable_impls: Vec<bool>,
+    reachable_invocations: Vec<bool>,
+    reachable_macros: BTreeSet<String>,
+    /// Names of everything mentioned by reachable code
+    names: BTreeSet<String>,
+    /// Paths mentioned by reachable code that are yet to be resolved
+    paths: ModulePaths,
+}
+
+impl<'ast> Reachability<'ast> {
+    fn new(ast: &'ast syn::File) -> Self {
+        let scopes = Scopes::new(ast);
+        let mut index = ItemIndex::default();
+        index.visit_file(ast);
+        let traits = index.traits();
+        let invocation_traits = index
+            .invocations
+            .iter()
+            .map(|(module, invocation)| {
+                let name = invocation.mac.path.get_ident()?.to_string();
+                let [(_, definition)] = index.macros.get(&name)?.as_slice() else {
+                    return None;
+                };
+                macro_impl_traits(definition.mac.tokens.clone())?
+                    .iter()
+                    .map(|path| {
+                        scopes
+                            .resolve_segments(module, path)
+                            .filter(|path| traits.contains_key(path))
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            reachable_impls: vec![false; index.impls.len()],
+            reachable_invocations: vec![false; index.invocations.len()],
+            scopes,
+            index,
+            traits,
+            invocation_traits,
+            reachable_items: BTreeSet::new(),
+            reachable_macros: BTreeSet::new(),
+            names: BTreeSet::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    fn references(&mut self, module: &ItemPath) -> ReferenceVisitor<'_> {
+        ReferenceVisitor {
+            module: module.clone(),
+            paths: &mut self.paths,
+            names: &mut self.names,
+        }
+    }
+
+    fn reach_item(&mut self, path: ItemPath) {
+        if !self.reachable_items.insert(path.clone()) {
+            return;
+        }
+        let module = &path[..path.len() - 1];
+        for item in self.index.items.get(&path).cloned().unwrap_or_default() {
+            syn::visit::visit_item(&mut self.references(&module.to_vec()), item);
+        }
+    }
+
+    /// Resolves the longest prefix of each pending path, so `Shape::Circle` reaches `Shape`.
+    fn resolve_paths(&mut self) {
+        while let Some((module, path)) = self.paths.pop() {
+            let resolved = (1..=path.len())
+                .rev()
+                .find_map(|len| self.scopes.resolve_segments(&module, &path[..len]));
+            if let Some(resolved) = resolved {
+                if self.index.items.contains_key(&resolved) {
+                    self.reach_item(resolved);
+                }
+            }
+        }
+    }
+
+    /// Whether everything local an impl is for is reachable, so its items might be used.
+    fn is_impl_reachable(&self, module: &ItemPath, item: &syn::ItemImpl) -> bool {
+        if let Some((_, trait_path, _)) = &item.trait_ {
+            if let Some(trait_path) = self.scopes.resolve(module, trait_path) {
+                if self.traits.contains_key(&trait_path)
+                    && !self.reachable_items.contains(&trait_path)
+                {
+                    return false;
+                }
+            }
+        }
+        let type_params: BTreeSet<&syn::Ident> =
+            item.generics.type_params().map(|p| &p.ident).collect();
+        let mut path_visitor = PathVisitor::default();
+        path_visitor.visit_type(&item.self_ty);
+        path_visitor.output_paths.into_iter().all(|path| {
+            if path
+                .segments
+                .first()
+                .is_some_and(|s| type_params.contains(&s.ident))
+            {
+                return true;
+            }
+            match self.scopes.resolve(module, path) {
+                Some(path) if self.index.items.contains_key(&path) => {
+                    self.reachable_items.contains(&path)
+                }
+                _ => true,
+            }
+        })
+    }
+
+    /// Visits everything reachable, until nothing new is found.
+    fn run(&mut self) {
+        let root = vec!["main".to_string()];
+        self.reach_item(root);
+        loop {
+            let before = (
+                self.reachable_items.len(),
+                self.reachable_impls.iter().filter(|r| **r).count(),
+                self.reachable_invocations.iter().filter(|r| **r).count(),
+                self.reachable_macros.len(),
+            );
+
+            for i in 0..self.index.invocations.len() {
+                let reachable = match &self.invocation_traits[i] {
+                    Some(traits) => traits.iter().any(|t| self.reachable_items.contains(t)),
+                    None => true,
+                };
+                if reachable && !self.reachable_invocations[i] {
+                    self.reachable_invocations[i] = true;
+                    let (module, invocation) = self.index.invocations[i].clone();
+                    self.references(&module).visit_macro(&invocation.mac);
+                }
+            }
+
+            let macros: Vec<String> = self
+                .index
+                .macros
+                .keys()
+                .filter(|name| self.names.contains(*name) && !self.reachable_macros.contains(*name))
+                .cloned()
+                .collect();
+            for name in macros {
+                for (module, definition) in self.index.macros[&name].clone() {
+                    self.references(&module).visit_macro(&definition.mac);
+                }
+                self.reachable_macros.insert(name);
+            }
+
+            self.resolve_paths();
+
+            let traits: Vec<ItemPath> = self
+                .traits
+                .iter()
+                .filter(|(path, names)| {
+                    !self.reachable_items.contains(*path) && !names.is_disjoint(&self.names)
+                })
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in traits {
+                self.reach_item(path);
+            }
+
+            for i in 0..self.index.impls.len() {
+                let (module, item) = &self.index.impls[i];
+                if !self.reachable_impls[i] && self.is_impl_reachable(module, item) {
+                    self.reachable_impls[i] = true;
+                    let (module, item) = (module.clone(), *item);
+                    syn::visit::visit_item_impl(&mut self.references(&module), item);
+                }
+            }
+
+            self.resolve_paths();
+
+            let after = (
+                self.reachable_items.len(),
+                self.reachable_impls.iter().filter(|r| **r).count(),
+                self.reachable_invocations.iter().filter(|r| **r).count(),
+                self.reachable_macros.len(),
+            );
+            if before == after {
+                return;
+            }
+        }
+    }
+
+    fn output_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        for (path, items) in &self.index.items {
+            if self.traits.contains_key(path) && !self.reachable_items.contains(path) {
+                ranges.extend(items.iter().map(|item| item.span().byte_range()));
+            }
+        }
+        for (module, item) in &self.index.impls {
+            let Some((_, trait_path, _)) = &item.trait_ else {
+                continue;
+            };
+            if self
+                .scopes
+                .resolve(module, trait_path)
+                .is_some_and(|trait_path| {
+                    self.traits.contains_key(&trait_path)
+                        && !self.reachable_items.contains(&trait_path)
+                })
+            {
+                ranges.push(item.span().byte_range());
+            }
+        }
+        for (i, (_, invocation)) in self.index.invocations.iter().enumerate() {
+            if !self.reachable_invocations[i] {
+                ranges.push(invocation.span().byte_range());
+            }
+        }
+        // Definitions of macros that were only invoked to implement unreachable traits
+        for (name, definitions) in &self.index.macros {
+            let is_invoked = self
+                .index
+                .invocations
+                .iter()
+                .any(|(_, invocation)| invocation.mac.path.is_ident(name));
+            if is_invoked && !self.reachable_macros.contains(name) {
+                ranges.extend(definitions.iter().map(|(_, item)| item.span().byte_range()));
+            }
+        }
+        ranges
+    }
+}
+
+/// Ranges of the local traits that code reachable from `main` never uses, along with their
+/// impls and the macro invocations generating them. rustc doesn't report a trait as dead as
+/// long as it has impls, which keeps traits like `One` alive in every bundle.
+pub(crate) fn unreachable_trait_ranges(ast: &syn::File) -> Vec<Range<usize>> {
+    let mut reachability = Reachability::new(ast);
+    if !reachability
+        .index
+        .items
+        .contains_key(&vec!["main".to_string()])
+    {
+        return Vec::new();
+    }
+    reachability.run();
+    reachability.output_ranges()
+}
+
+/// Local traits with the names of their items.
+pub(crate) fn local_traits(ast: &syn::File) -> BTreeMap<ItemPath, BTreeSet<String>> {
+    let mut index = ItemIndex::default();
+    index.visit_file(ast);
+    index.traits()
+}
+
+/// Collects the names that paths, method calls and macro invocations mention anywhere in a
+/// file, unlike [`ReferenceVisitor`] which stops at nested items.
+#[derive(Default)]
+struct MentionVisitor {
+    names: BTreeSet<String>,
+}
+
+impl<'ast> Visit<'ast> for MentionVisitor {
+    fn visit_path_segment(&mut self, i: &'ast syn::PathSegment) {
+        self.names.insert(i.ident.to_string());
+        syn::visit::visit_path_segment(self, i);
+    }
+
+    fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+        self.names.insert(i.method.to_string());
+        syn::visit::visit_expr_method_call(self, i);
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        self.visit_path(&i.path);
+        token_paths(i.tokens.clone(), &mut Vec::new(), &mut self.names);
+    }
+}
+
+/// Names mentioned by the paths, method calls and macro invocations of a file.
+pub(crate) fn mentioned_names(ast: &syn::File) -> BTreeSet<String> {
+    let mut visitor = MentionVisitor::default();
+    visitor.visit_file(ast);
+    visitor.names
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeSet;
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, visit::Visit};
+
+/// Something the code can use that only works from some version of Rust on.
+enum Feature {
+    /// A method of std, called like `.name()`
+    Method(&'static str),
+    /// A std item, matched against consecutive segments of paths like `std::iter::repeat_n`
+    Path(&'static [&'static str]),
+    LetElse,
+    LetChains,
+    InlineConst,
+    ExclusiveRangePattern,
+}
+
+/// Versions of Rust that stabilized std items and syntax that come in handy in contests.
+const STABILIZATIONS: &[(Feature, &str)] = &[
+    (Feature::Method("unsigned_abs"), "1.51.0"),
+    (Feature::Path(&["iter", "zip"]), "1.59.0"),
+    (Feature::Method("abs_diff"), "1.60.0"),
+    (Feature::Method("retain_mut"), "1.61.0"),
+    (Feature::Path(&["array", "from_fn"]), "1.63.0"),
+    (Feature::LetElse, "1.65.0"),
+    (Feature::Path(&["io", "read_to_string"]), "1.65.0"),
+    (Feature::Method("pop_first"), "1.66.0"),
+    (Feature::Method("pop_last"), "1.66.0"),
+    (Feature::Method("first_key_value"), "1.66.0"),
+    (Feature::Method("last_key_value"), "1.66.0"),
+    (Feature::Method("checked_add_signed"), "1.66.0"),
+    (Feature::Method("saturating_add_signed"), "1.66.0"),
+    (Feature::Method("wrapping_add_signed"), "1.66.0"),
+    (Feature::Path(&["hint", "black_box"]), "1.66.0"),
+    (Feature::Method("ilog"), "1.67.0"),
+    (Feature::Method("ilog2"), "1.67.0"),
+    (Feature::Method("ilog10"), "1.67.0"),
+    (Feature::Method("checked_ilog2"), "1.67.0"),
+    (Feature::Method("checked_ilog10"), "1.67.0"),
+    (Feature::Method("is_some_and"), "1.70.0"),
+    (Feature::Method("is_ok_and"), "1.70.0"),
+    (Feature::Method("is_err_and"), "1.70.0"),
+    (Feature::Path(&["OnceCell"]), "1.70.0"),
+    (Feature::Path(&["OnceLock"]), "1.70.0"),
+    (Feature::Method("div_ceil"), "1.73.0"),
+    (Feature::Method("next_multiple_of"), "1.73.0"),
+    (Feature::Method("checked_next_multiple_of"), "1.73.0"),
+    (Feature::Method("inspect_err"), "1.76.0"),
+    (Feature::Method("chunk_by"), "1.77.0"),
+    (Feature::Method("first_chunk"), "1.77.0"),
+    (Feature::Method("last_chunk"), "1.77.0"),
+    (Feature::InlineConst, "1.79.0"),
+    (Feature::ExclusiveRangePattern, "1.80.0"),
+    (Feature::Method("split_at_checked"), "1.80.0"),
+    (Feature::Method("take_if"), "1.80.0"),
+    (Feature::Method("as_flattened"), "1.80.0"),
+    (Feature::Path(&["LazyCell"]), "1.80.0"),
+    (Feature::Path(&["LazyLock"]), "1.80.0"),
+    (Feature::Method("is_sorted"), "1.82.0"),
+    (Feature::Method("is_sorted_by"), "1.82.0"),
+    (Feature::Method("is_sorted_by_key"), "1.82.0"),
+    (Feature::Method("is_none_or"), "1.82.0"),
+    (Feature::Path(&["iter", "repeat_n"]), "1.82.0"),
+    (Feature::Method("isqrt"), "1.84.0"),
+    (Feature::Method("midpoint"), "1.85.0"),
+    (Feature::Method("pop_if"), "1.86.0"),
+    (Feature::Method("get_disjoint_mut"), "1.86.0"),
+    (Feature::Method("extract_if"), "1.87.0"),
+    (Feature::Method("cast_signed"), "1.87.0"),
+    (Feature::Method("cast_unsigned"), "1.87.0"),
+    (Feature::LetChains, "1.88.0"),
+];
+
+/// Parses versions like `1.70` or `1.70.0`.
+fn parse_version(version: &str) -> anyhow::Result<(u32, u32, u32)> {
+    let parts: Vec<u32> = version
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow::anyhow!("Invalid Rust version {version}"))?;
+    match parts[..] {
+        [major, minor] => Ok((major, minor, 0)),
+        [major, minor, patch] => Ok((major, minor, patch)),
+        _ => anyhow::bail!("Invalid Rust version {version}"),
+    }
+}
+
+/// A use of something newer than the Rust version of the judge.
+pub struct NewerFeature {
+    pub description: String,
+    /// Version of Rust that stabilized it
+    pub version: &'static str,
+    pub span: Span,
+    /// Whether it only might be the std item. Methods are matched by name, so a method of a
+    /// trait from another crate with the same name looks the same.
+    pub is_possible_match: bool,
+}
+
+/// Names of the functions and types the code defines itself, which shadow the ones of std.
+#[derive(Default)]
+struct LocalNameVisitor {
+    output_names: BTreeSet<String>,
+}
+
+impl<'ast> Visit<'ast> for LocalNameVisitor {
+    fn visit_signature(&mut self, i: &'ast syn::Signature) {
+        self.output_names.insert(i.ident.to_string());
+        syn::visit::visit_signature(self, i);
+    }
+
+    fn visit_item_struct(&mut self, i: &'ast syn::ItemStruct) {
+        self.output_names.insert(i.ident.to_string());
+        syn::visit::visit_item_struct(self, i);
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast syn::ItemEnum) {
+        self.output_names.insert(i.ident.to_string());
+        syn::visit::visit_item_enum(self, i);
+    }
+
+    fn visit_item_type(&mut self, i: &'ast syn::ItemType) {
+        self.output_names.insert(i.ident.to_string());
+        syn::visit::visit_item_type(self, i);
+    }
+}
+
+struct NewerFeatureVisitor<'a> {
+    rust_version: (u32, u32, u32),
+    local_names: &'a BTreeSet<String>,
+    in_let_chain: bool,
+    output_features: Vec<NewerFeature>,
+}
+
+impl NewerFeatureVisitor<'_> {
+    fn push(&mut self, is_feature: impl Fn(&Feature) -> bool, description: String, span: Span) {
+        for (feature, version) in STABILIZATIONS {
+            let is_newer =
+                parse_version(version).expect("valid stabilization version") > self.rust_version;
+            if is_newer && is_feature(feature) {
+                self.output_features.push(NewerFeature {
+                    description: description.clone(),
+                    version,
+                    span,
+                    is_possible_match: matches!(feature, Feature::Method(_)),
+                });
+            }
+        }
+    }
+
+    fn visit_segments(&mut self, segments: &[String], span: Span) {
+        let local_names = self.local_names;
+        let is_feature = |feature: &Feature| match feature {
+            Feature::Path(path) => {
+                let is_local = path.last().is_some_and(|name| local_names.contains(*name));
+                !is_local
+                    && segments
+                        .windows(path.len())
+                        .any(|window| window.iter().zip(path.iter()).all(|(a, b)| a == b))
+            }
+            _ => false,
+        };
+        self.push(is_feature, format!("`{}`", segments.join("::")), span);
+    }
+
+    fn visit_use_tree_segments(&mut self, tree: &syn::UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            syn::UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.visit_use_tree_segments(&path.tree, prefix);
+                prefix.pop();
+            }
+            syn::UseTree::Name(syn::UseName { ident })
+            | syn::UseTree::Rename(syn::UseRename { ident, .. }) => {
+                prefix.push(ident.to_string());
+                self.visit_segments(prefix, ident.span());
+                prefix.pop();
+            }
+            syn::UseTree::Glob(_) => (),
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.visit_use_tree_segments(tree, prefix);
+                }
+            }
+        }
+    }
+}
+
+/// Whether `expr` is a `let` or a chain of conditions joined by `&&` with a `let` in it.
+fn has_let(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Let(_) => true,
+        syn::Expr::Binary(binary) if matches!(binary.op, syn::BinOp::And(_)) => {
+            has_let(&binary.left) || has_let(&binary.right)
+        }
+        _ => false,
+    }
+}
+
+impl<'ast> Visit<'ast> for NewerFeatureVisitor<'_> {
+    fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+        let method = i.method.to_string();
+        if !self.local_names.contains(&method) {
+            self.push(
+                |f| matches!(f, Feature::Method(name) if *name == method),
+                format!("`.{method}()`"),
+                i.method.span(),
+            );
+        }
+        syn::visit::visit_expr_method_call(self, i);
+    }
+
+    fn visit_path(&mut self, i: &'ast syn::Path) {
+        let segments: Vec<String> = i.segments.iter().map(|s| s.ident.to_string()).collect();
+        self.visit_segments(&segments, i.span());
+        syn::visit::visit_path(self, i);
+    }
+
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        self.visit_use_tree_segments(&i.tree, &mut Vec::new());
+    }
+
+    /// Arguments of macros like `println!` and `vec!` are expressions too.
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        let parser = syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+        if let Ok(args) = i.parse_body_with(parser) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        }
+        syn::visit::visit_macro(self, i);
+    }
+
+    fn visit_local(&mut self, i: &'ast syn::Local) {
+        if i.init.as_ref().is_some_and(|init| init.diverge.is_some()) {
+            self.push(
+                |f| matches!(f, Feature::LetElse),
+                "`let ... else`".to_string(),
+                i.let_token.span,
+            );
+        }
+        syn::visit::visit_local(self, i);
+    }
+
+    fn visit_expr_binary(&mut self, i: &'ast syn::ExprBinary) {
+        let is_let_chain =
+            matches!(i.op, syn::BinOp::And(_)) && (has_let(&i.left) || has_let(&i.right));
+        if !is_let_chain || self.in_let_chain {
+            syn::visit::visit_expr_binary(self, i);
+            return;
+        }
+        self.push(
+            |f| matches!(f, Feature::LetChains),
+            "a `let` chain".to_string(),
+            i.op.span(),
+        );
+        // Only the outermost `&&` of a chain is reported.
+        self.in_let_chain = true;
+        syn::visit::visit_expr_binary(self, i);
+        self.in_let_chain = false;
+    }
+
+    fn visit_expr_const(&mut self, i: &'ast syn::ExprConst) {
+        self.push(
+            |f| matches!(f, Feature::InlineConst),
+            "an inline `const` block".to_string(),
+            i.const_token.span,
+        );
+        syn::visit::visit_expr_const(self, i);
+    }
+
+    fn visit_pat(&mut self, i: &'ast syn::Pat) {
+        if let syn::Pat::Range(range) = i {
+            if matches!(range.limits, syn::RangeLimits::HalfOpen(_)) && range.end.is_some() {
+                self.push(
+                    |f| matches!(f, Feature::ExclusiveRangePattern),
+                    "an exclusive range pattern".to_string(),
+                    range.span(),
+                );
+            }
+        }
+        syn::visit::visit_pat(self, i);
+    }
+}
+
+/// Finds the std items and syntax `src` uses that were stabilized after `rust_version`.
+pub fn newer_features(src: &str, rust_version: &str) -> anyhow::Result<Vec<NewerFeature>> {
+    let ast = syn::parse_file(src)?;
+    let mut local_name_visitor = LocalNameVisitor::default();
+    local_name_visitor.visit_file(&ast);
+    let mut visitor = NewerFeatureVisitor {
+        rust_version: parse_version(rust_version)?,
+        local_names: &local_name_visitor.output_names,
+        in_let_chain: false,
+        output_features: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+    let mut reported = BTreeSet::new();
+    let mut features = visitor.output_features;
+    // A line like `let x: OnceLock<u32> = OnceLock::new();` only needs to be reported once.
+    features.retain(|f| reported.insert((f.span.start().line, f.version)));
+    Ok(features)
+}
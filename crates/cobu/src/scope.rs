@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use syn::visit::Visit;
+
+/// Path of an item from the crate root, like `["graph", "Node"]` for `crate::graph::Node`.
+pub(crate) type ItemPath = Vec<String>;
+
+/// Names visible inside a module.
+#[derive(Default)]
+struct ModuleScope {
+    /// Items defined in the module, including its child modules
+    items: BTreeSet<String>,
+    /// The items that are traits, which can be used without being named
+    traits: BTreeSet<String>,
+    /// Names brought into scope by `use`, with the paths they refer to, relative to the module
+    imports: BTreeMap<String, Vec<String>>,
+    /// Modules glob imported with `use path::*`, relative to the module
+    globs: Vec<Vec<String>>,
+}
+
+/// Resolves paths to the items defined in a single file crate, the way rustc would for the
+/// common cases: `crate`, `self` and `super` prefixes, child modules, `use` imports with
+/// renames, groups and globs. Items and imports inside function bodies are treated as if they
+/// were in the enclosing module, and paths to anything outside the crate don't resolve.
+#[derive(Default)]
+pub(crate) struct Scopes {
+    modules: BTreeMap<ItemPath, ModuleScope>,
+}
+
+/// Deeper `use` chains are almost certainly cycles.
+const MAX_RESOLVE_DEPTH: usize = 32;
+
+impl Scopes {
+    pub(crate) fn new(file: &syn::File) -> Self {
+        let mut visitor = ScopeVisitor::default();
+        visitor.visit_file(file);
+        visitor.scopes
+    }
+
+    /// Resolves a path written inside `module` to the path of the item it refers to.
+    pub(crate) fn resolve(&self, module: &[String], path: &syn::Path) -> Option<ItemPath> {
+        if path.leading_colon.is_some() {
+            return None;
+        }
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        self.resolve_segments(module, &segments)
+    }
+
+    /// Like [`Self::resolve`] for a path given as its segments.
+    pub(crate) fn resolve_segments(
+        &self,
+        module: &[String],
+        segments: &[String],
+    ) -> Option<ItemPath> {
+        self.resolve_segments_with_depth(module, segments, 0)
+    }
+
+    fn resolve_segments_with_depth(
+        &self,
+        module: &[String],
+        segments: &[String],
+        depth: usize,
+    ) -> Option<ItemPath> {
+        if depth > MAX_RESOLVE_DEPTH {
+            return None;
+        }
+        let (first, rest) = segments.split_first()?;
+        let mut current: ItemPath = match first.as_str() {
+            "crate" => Vec::new(),
+            "self" => module.to_vec(),
+            "super" => module.split_last()?.1.to_vec(),
+            name => self.resolve_name(module, name, depth)?,
+        };
+        for segment in rest {
+            match segment.as_str() {
+                "super" => {
+                    current.pop()?;
+                }
+                "self" => (),
+                name => current = self.resolve_name(&current, name, depth)?,
+            }
+        }
+        Some(current)
+    }
+
+    /// Whether a resolved path is a trait.
+    pub(crate) fn is_trait(&self, path: &[String]) -> bool {
+        path.split_last().is_some_and(|(name, module)| {
+            self.modules
+                .get(module)
+                .is_some_and(|scope| scope.traits.contains(name))
+        })
+    }
+
+    /// Resolves a name in the scope of `module`.
+    fn resolve_name(&self, module: &[String], name: &str, depth: usize) -> Option<ItemPath> {
+        let scope = self.modules.get(module)?;
+        if scope.items.contains(name) {
+            let mut path = module.to_vec();
+            path.push(name.to_string());
+            return Some(path);
+        }
+        if let Some(target) = scope.imports.get(name) {
+            return self.resolve_segments_with_depth(module, target, depth + 1);
+        }
+        scope.globs.iter().find_map(|glob| {
+            let glob_module = self.resolve_segments_with_depth(module, glob, depth + 1)?;
+            self.resolve_name(&glob_module, name, depth + 1)
+        })
+    }
+}
+
+#[derive(Default)]
+struct ScopeVisitor {
+    module: ItemPath,
+    scopes: Scopes,
+}
+
+impl ScopeVisitor {
+    fn scope(&mut self) -> &mut ModuleScope {
+        self.scopes.modules.entry(self.module.clone()).or_default()
+    }
+
+    fn visit_use_tree_with_prefix(&mut self, tree: &syn::UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            syn::UseTree::Path(use_path) => {
+                prefix.push(use_path.ident.to_string());
+                self.visit_use_tree_with_prefix(&use_path.tree, prefix);
+                prefix.pop();
+            }
+            syn::UseTree::Name(use_name) => {
+                let mut target = prefix.clone();
+                let name = if use_name.ident == "self" {
+                    let Some(name) = prefix.last() else {
+                        return;
+                    };
+                    name.clone()
+                } else {
+                    target.push(use_name.ident.to_string());
+                    use_name.ident.to_string()
+                };
+                self.scope().imports.insert(name, target);
+            }
+            syn::UseTree::Rename(use_rename) => {
+                if use_rename.rename == "_" {
+                    return;
+                }
+                let mut target = prefix.clone();
+                if use_rename.ident != "self" {
+                    target.push(use_rename.ident.to_string());
+                }
+                self.scope()
+                    .imports
+                    .insert(use_rename.rename.to_string(), target);
+            }
+            syn::UseTree::Glob(_) => {
+                let glob = prefix.clone();
+                self.scope().globs.push(glob);
+            }
+            syn::UseTree::Group(use_group) => {
+                for tree in &use_group.items {
+                    self.visit_use_tree_with_prefix(tree, prefix);
+                }
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ScopeVisitor {
+    fn visit_item(&mut self, i: &'ast syn::Item) {
+        let ident = match i {
+            syn::Item::Const(item) => Some(&item.ident),
+            syn::Item::Enum(item) => Some(&item.ident),
+            syn::Item::Fn(item) => Some(&item.sig.ident),
+            syn::Item::Macro(item) => item.ident.as_ref(),
+            syn::Item::Mod(item) => Some(&item.ident),
+            syn::Item::Static(item) => Some(&item.ident),
+            syn::Item::Struct(item) => Some(&item.ident),
+            syn::Item::Trait(item) => Some(&item.ident),
+            syn::Item::TraitAlias(item) => Some(&item.ident),
+            syn::Item::Type(item) => Some(&item.ident),
+            syn::Item::Union(item) => Some(&item.ident),
+            _ => None,
+        };
+        if let Some(ident) = ident {
+            self.scope().items.insert(ident.to_string());
+        }
+        if let syn::Item::Trait(syn::ItemTrait { ident, .. })
+        | syn::Item::TraitAlias(syn::ItemTraitAlias { ident, .. }) = i
+        {
+            self.scope().traits.insert(ident.to_string());
+        }
+        syn::visit::visit_item(self, i);
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.module.push(i.ident.to_string());
+        self.scope();
+        syn::visit::visit_item_mod(self, i);
+        self.module.pop();
+    }
+
+    fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
+        if i.leading_colon.is_none() {
+            self.visit_use_tree_with_prefix(&i.tree, &mut Vec::new());
+        }
+    }
+}
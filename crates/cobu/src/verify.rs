@@ -0,0 +1,186 @@
+use std::{
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+use anyhow::Context;
+use cargo_metadata::{Message, Target};
+
+fn run_command(mut command: Command) -> anyhow::Result<()> {
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{command:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+}
+
+/// Builds `bin` of `package` in release mode and returns the path of its executable.
+fn build_original(manifest_path: &Path, package: &str, bin: &Target) -> anyhow::Result<PathBuf> {
+    let mut cargo = Command::new(std::env::var("CARGO").unwrap_or("cargo".to_string()));
+    cargo
+        .args(["build", "--release", "--quiet", "--message-format=json"])
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .args(["--package", package, "--bin", &bin.name]);
+    let output = cargo
+        .output()
+        .with_context(|| format!("Failed to run {cargo:?}"))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{cargo:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Message::parse_stream(output.stdout.as_slice())
+        .filter_map(|message| match message {
+            Ok(Message::CompilerArtifact(artifact))
+                if artifact.target.is_bin() && artifact.target.name == bin.name =>
+            {
+                artifact.executable
+            }
+            _ => None,
+        })
+        .last()
+        .map(PathBuf::from)
+        .with_context(|| format!("cargo didn't report an executable for {}", bin.name))
+}
+
+/// Runs `executable` with `input` as stdin and returns its stdout.
+fn run_on_input(executable: &Path, input: &[u8]) -> anyhow::Result<String> {
+    let mut child = Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", executable.display()))?;
+    let mut stdin = child.stdin.take().context("Failed to open stdin")?;
+    // Writing while the output is read, or a child that prints before reading all its input
+    // could fill the pipes and block both processes. A child that exits without reading all
+    // its input closes stdin early, which is up to its exit status to report.
+    let output = std::thread::scope(|scope| -> anyhow::Result<Output> {
+        let writer = scope.spawn(move || match stdin.write_all(input) {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        });
+        let output = child.wait_with_output()?;
+        writer.join().expect("Writing stdin panicked")?;
+        Ok(output)
+    })?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{} exited with {}:\n{}",
+        executable.display(),
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Describes the first line where two outputs differ, or `None` if they are the same.
+fn first_difference(original: &str, bundled: &str) -> Option<String> {
+    if original == bundled {
+        return None;
+    }
+    let mut original_lines = original.lines();
+    let mut bundled_lines = bundled.lines();
+    for line_number in 1.. {
+        match (original_lines.next(), bundled_lines.next()) {
+            (Some(a), Some(b)) if a == b => continue,
+            (None, None) => break,
+            (a, b) => {
+                return Some(format!(
+                    "line {line_number}: original printed {:?}, bundle printed {:?}",
+                    a.unwrap_or("nothing"),
+                    b.unwrap_or("nothing")
+                ))
+            }
+        }
+    }
+    Some("outputs differ in trailing whitespace".to_string())
+}
+
+/// Inputs of `bin`, the `*.in` files of `tests_dir/<bin>`, sorted by name.
+fn bin_inputs(tests_dir: &Path, bin: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = tests_dir.join(bin);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut inputs = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "in") {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Builds the original and the bundled version of each of `bins`, runs both on the inputs in
+/// `tests_dir/<bin>/*.in` and fails if their outputs differ on any of them.
+pub(crate) fn verify_bins(
+    manifest_path: &Path,
+    package: &str,
+    bins: &[Target],
+    edition: &str,
+    out_dir: &Path,
+    tests_dir: &Path,
+    target_dir: &Path,
+) -> anyhow::Result<()> {
+    let bundle_dir = target_dir.join("cobu-verify");
+    std::fs::create_dir_all(&bundle_dir)?;
+    let mut failures = Vec::new();
+    for bin in bins {
+        let inputs = bin_inputs(tests_dir, &bin.name)?;
+        if inputs.is_empty() {
+            eprintln!(
+                "No inputs for {} in {}",
+                bin.name,
+                tests_dir.join(&bin.name).display()
+            );
+            continue;
+        }
+
+        let original = build_original(manifest_path, package, bin)?;
+
+        let bundled = bundle_dir
+            .join(&bin.name)
+            .with_extension(std::env::consts::EXE_EXTENSION);
+        let mut rustc = Command::new("rustc");
+        rustc
+            .args(["--edition", edition, "-O", "-o"])
+            .arg(&bundled)
+            .arg(out_dir.join(&bin.name).with_extension("rs"));
+        run_command(rustc)?;
+
+        for input_path in inputs {
+            let input = std::fs::read(&input_path)?;
+            let difference = match (
+                run_on_input(&original, &input),
+                run_on_input(&bundled, &input),
+            ) {
+                (Ok(original_output), Ok(bundled_output)) => {
+                    first_difference(&original_output, &bundled_output)
+                }
+                (Err(e), _) => Some(format!("original failed: {e:#}")),
+                (_, Err(e)) => Some(format!("bundle failed: {e:#}")),
+            };
+            match difference {
+                Some(difference) => {
+                    failures.push(format!("{}: {difference}", input_path.display()))
+                }
+                None => eprintln!("{} passed on {}", bin.name, input_path.display()),
+            }
+        }
+    }
+    anyhow::ensure!(
+        failures.is_empty(),
+        "Bundles behave differently from the original binaries:\n{}",
+        failures.join("\n")
+    );
+    Ok(())
+}
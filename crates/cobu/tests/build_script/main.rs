@@ -0,0 +1,74 @@
+#[path = "../common/mod.rs"]
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const MANIFEST_DIR_VAR: &str = "COBU_TEST_BUILD_SCRIPT_MANIFEST_DIR";
+
+/// Runs the build script the way cargo would. Only does something when started by
+/// [`build_script_stdout`].
+#[test]
+fn run_build_script() {
+    let Ok(manifest_dir) = std::env::var(MANIFEST_DIR_VAR) else {
+        return;
+    };
+    std::env::set_var("CARGO_MANIFEST_DIR", manifest_dir);
+    cobu::build_script().unwrap();
+}
+
+/// Runs the build script of the package in `dir` in a child process and returns what it
+/// printed to stdout, which is what cargo reads.
+fn build_script_stdout(dir: &Path) -> String {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "run_build_script", "--nocapture"])
+        .env(MANIFEST_DIR_VAR, dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn build_script_reruns_on_manifest_and_sources() {
+    let dir = common::fixture("build_script", &["app"], "build_script").join("app");
+    let stdout = build_script_stdout(&dir);
+    // The test harness prints the name of the test on the same line as the first one.
+    let rerun_paths: Vec<PathBuf> = stdout
+        .lines()
+        .filter_map(|line| line.split_once("cargo:rerun-if-changed="))
+        .map(|(_, path)| PathBuf::from(path))
+        .collect();
+    assert_eq!(
+        rerun_paths,
+        vec![
+            dir.join("Cargo.toml"),
+            dir.join("src").join("bin"),
+            dir.join("src").join("bin").join("a.rs"),
+        ]
+    );
+    assert!(dir.join("dist").join("a.rs").is_file());
+}
+
+#[test]
+fn rust_version_warnings_reach_cargo_even_when_cached() {
+    let dir =
+        common::fixture("build_script", &["old_judge"], "build_script_old_judge").join("old_judge");
+    let bundle = dir.join("dist").join("a.rs");
+    for _ in 0..2 {
+        let stdout = build_script_stdout(&dir);
+        let warnings: Vec<&str> = stdout
+            .lines()
+            .filter_map(|line| line.split_once("cargo:warning="))
+            .map(|(_, warning)| warning)
+            .collect();
+        assert_eq!(warnings.len(), 3, "{stdout}");
+        assert!(
+            warnings[0].contains("`.div_ceil()` might need Rust 1.73.0, but the judge runs 1.0.0")
+        );
+        assert!(warnings[1].starts_with(&format!("  --> {}:", bundle.display())));
+        assert!(warnings[2].starts_with("  = note: comes from "));
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    println!("{}", 7u32.div_ceil(2));
+}
@@ -0,0 +1,65 @@
+#[path = "../common/mod.rs"]
+mod common;
+
+use std::path::Path;
+
+const UNTOUCHED: &str = "// untouched\n";
+
+fn bundle(dir: &Path, minify: bool) {
+    cobu::cli(cobu::Args {
+        manifest_path: Some(dir.join("app").join("Cargo.toml")),
+        out_dir: dir.join("dist"),
+        offline: true,
+        minify,
+        ..Default::default()
+    })
+    .unwrap();
+}
+
+/// Marks both bundles, so the next run shows which ones it wrote again.
+fn mark_bundles(dir: &Path) {
+    for bin in ["uses_lib", "no_lib"] {
+        std::fs::write(dir.join("dist").join(format!("{bin}.rs")), UNTOUCHED).unwrap();
+    }
+}
+
+fn is_untouched(dir: &Path, bin: &str) -> bool {
+    std::fs::read_to_string(dir.join("dist").join(format!("{bin}.rs"))).unwrap() == UNTOUCHED
+}
+
+#[test]
+fn unchanged_bundles_are_skipped() {
+    let dir = common::fixture("cache", &["app", "helper"], "unchanged");
+    bundle(&dir, false);
+    assert!(!is_untouched(&dir, "uses_lib"));
+    assert!(!dir.join("dist").join(".cobu-cache.json").exists());
+    assert!(dir.join("app").join("target").join("cobu").is_dir());
+
+    mark_bundles(&dir);
+    bundle(&dir, false);
+    assert!(is_untouched(&dir, "uses_lib"));
+    assert!(is_untouched(&dir, "no_lib"));
+}
+
+#[test]
+fn editing_a_lib_only_rebundles_its_users() {
+    let dir = common::fixture("cache", &["app", "helper"], "edited_lib");
+    bundle(&dir, false);
+    mark_bundles(&dir);
+    let lib = dir.join("helper").join("src").join("lib.rs");
+    let contents = std::fs::read_to_string(&lib).unwrap();
+    std::fs::write(&lib, contents.replace("x * 2", "x + x")).unwrap();
+    bundle(&dir, false);
+    assert!(!is_untouched(&dir, "uses_lib"));
+    assert!(is_untouched(&dir, "no_lib"));
+}
+
+#[test]
+fn changing_an_option_rebundles_everything() {
+    let dir = common::fixture("cache", &["app", "helper"], "changed_option");
+    bundle(&dir, false);
+    mark_bundles(&dir);
+    bundle(&dir, true);
+    assert!(!is_untouched(&dir, "uses_lib"));
+    assert!(!is_untouched(&dir, "no_lib"));
+}
@@ -0,0 +1,92 @@
+use std::str::FromStr;
+
+use cobu::Cfg;
+
+fn cfgs(specs: &[&str]) -> Vec<Cfg> {
+    specs.iter().map(|s| Cfg::from_str(s).unwrap()).collect()
+}
+
+#[test]
+fn override_target_family() {
+    let target_cfg = cobu::override_target_cfg(
+        cfgs(&[
+            "windows",
+            "target_family = \"windows\"",
+            "target_os = \"windows\"",
+            "panic = \"unwind\"",
+        ]),
+        &cfgs(&[
+            "target_family = \"unix\"",
+            "target_os = \"linux\"",
+            "debug_assertions",
+        ]),
+    );
+    assert_eq!(
+        target_cfg,
+        cfgs(&[
+            "panic = \"unwind\"",
+            "unix",
+            "target_family = \"unix\"",
+            "target_os = \"linux\"",
+            "debug_assertions",
+        ])
+    );
+}
+
+#[test]
+fn keys_the_judge_does_not_define_are_left_untouched() {
+    let src = r#"#[cfg(target_feature = "avx2")]
+fn simd() {}
+#[cfg(target_has_atomic = "64")]
+fn atomic() {}
+#[cfg(not(target_env = "msvc"))]
+fn gnu() {}
+#[cfg(all(unix, target_vendor = "pc"))]
+fn unix_pc() {}
+#[cfg(any(windows, target_vendor = "apple"))]
+fn windows_or_apple() {}
+fn main() {
+    let _ = cfg!(target_feature = "sse2");
+}
+"#;
+    assert_eq!(
+        cobu::strip_cfgs(src.to_string(), &cobu::default_target_cfg()).unwrap(),
+        r#"#[cfg(target_feature = "avx2")]
+fn simd() {}
+#[cfg(target_has_atomic = "64")]
+fn atomic() {}
+#[cfg(not(target_env = "msvc"))]
+fn gnu() {}
+
+
+fn windows_or_apple() {}
+fn main() {
+    let _ = cfg!(target_feature = "sse2");
+}
+"#
+    );
+}
+
+#[test]
+fn attributes_from_cfg_attr_are_evaluated_too() {
+    let src = r#"#[cfg_attr(windows, cfg_attr(target_pointer_width = "64", derive(Debug)))]
+struct Wide;
+#[cfg_attr(windows, cfg_attr(unix, derive(Debug)))]
+struct Narrow;
+#[cfg_attr(windows, cfg(unix))]
+struct Unix;
+#[cfg_attr(windows, cfg(target_feature = "avx2"), derive(Clone))]
+struct Simd;
+"#;
+    assert_eq!(
+        cobu::strip_cfgs(src.to_string(), &cobu::default_target_cfg()).unwrap(),
+        r#"#[derive(Debug)]
+struct Wide;
+
+struct Narrow;
+
+#[cfg(target_feature = "avx2")] #[derive(Clone)]
+struct Simd;
+"#
+    );
+}
@@ -0,0 +1,14 @@
+#[cfg(windows)]
+fn bin_count() -> u32 {
+    let n: u32 = "two";
+    n
+}
+
+#[cfg(unix)]
+fn bin_count() -> u32 {
+    2
+}
+
+fn main() {
+    println!("{} {} {}", helper::platform(), helper::count(), bin_count());
+}
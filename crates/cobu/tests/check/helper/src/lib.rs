@@ -0,0 +1,22 @@
+#[cfg(unix)]
+pub fn platform() -> &'static str {
+    "unix"
+}
+
+// Never compiled on this machine, so nothing caught the mistakes.
+#[cfg(windows)]
+pub fn platform() -> &'static str {
+    let name: &'static str = 7;
+    name
+}
+
+#[cfg(unix)]
+pub fn count() -> u32 {
+    2
+}
+
+#[cfg(windows)]
+pub fn count() -> u32 {
+    let n: u32 = "two";
+    n
+}
@@ -0,0 +1,28 @@
+#[path = "../common/mod.rs"]
+mod common;
+
+#[cfg(unix)]
+#[test]
+fn errors_point_at_the_original_source() {
+    let dir = common::fixture("check", &["app", "helper"], "check");
+    // The Windows code the bundle is made from never compiled here.
+    let error = cobu::cli(cobu::Args {
+        manifest_path: Some(dir.join("app").join("Cargo.toml")),
+        out_dir: dir.join("dist"),
+        offline: true,
+        ..Default::default()
+    })
+    .unwrap_err();
+    let error = format!("{error:#}");
+    let lib = dir.join("helper").join("src").join("lib.rs");
+    assert!(error.contains("expected `&str`, found integer"));
+    assert!(error.contains(&format!("= note: comes from {}:9", lib.display())));
+    // `let n: u32 = "two";` is both in the binary and the library.
+    assert_eq!(
+        error
+            .matches("= note: the line it comes from is unknown")
+            .count(),
+        2
+    );
+    assert!(!dir.join("dist").join("app.rs").exists());
+}
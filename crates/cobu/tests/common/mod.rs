@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()));
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+/// Copies the fixture packages `tests/<test>/<package>` side by side into a fresh directory
+/// `name`, so their builds and edits stay out of the sources.
+pub fn fixture(test: &str, packages: &[&str], name: &str) -> PathBuf {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    for package in packages {
+        copy_dir(
+            &manifest_dir.join("tests").join(test).join(package),
+            &dir.join(package),
+        );
+    }
+    dir
+}
@@ -0,0 +1,29 @@
+mod grid {
+    pub(crate) struct Grid {
+        inner: Vec<u32>,
+        cols: usize,
+    }
+
+    impl Grid {
+        pub(crate) fn new(rows: usize, cols: usize) -> Grid {
+            Grid {
+                inner: vec![0; rows * cols],
+                cols,
+            }
+        }
+
+        fn inner_index(&self, index: (usize, usize)) -> usize {
+            index.0 * self.cols + index.1
+        }
+
+        pub(crate) fn get(&self, index: (usize, usize)) -> u32 {
+            self.inner[self.inner_index(index)]
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let grid = grid::Grid::new(2, 3);
+    println!("{}", grid.get((1, 2)));
+}
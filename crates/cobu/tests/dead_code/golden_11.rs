@@ -0,0 +1,66 @@
+mod counter {
+    pub(crate) struct Counter {
+        count: u64,
+        step: u64,
+    }
+
+    impl Counter {
+        pub(crate) fn new() -> Self {
+            Self { count: 0, step: 1 }
+        }
+
+        pub(crate) fn increment(&mut self) {
+            self.count += self.step;
+        }
+
+        pub(crate) fn count(&self) -> u64 {
+            self.count
+        }
+    }
+}
+
+mod shape {
+    pub(crate) enum Shape {
+        Circle(u32),
+        Triangle(u32, u32),
+    }
+
+    pub(crate) enum Color {
+        Red,
+        Green,
+    }
+
+    pub(crate) fn area(shape: &Shape) -> u32 {
+        match shape {
+            Shape::Circle(r) => 3 * r * r,
+            Shape::Triangle(a, _) => a * a,
+        }
+    }
+
+    pub(crate) fn perimeter(shape: &Shape) -> u32 {
+        match shape {
+            Shape::Circle(r) => 6 * r,
+            Shape::Triangle(a, b) => 2 * a + b,
+        }
+    }
+
+    pub(crate) fn color_code(color: Color) -> u32 {
+        match color {
+            Color::Red => 0,
+            Color::Green => 1,
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let mut counter = counter::Counter::new();
+    counter.increment();
+    println!("{}", counter.count());
+    let shapes = [shape::Shape::Circle(1), shape::Shape::Triangle(2, 3)];
+    for s in &shapes {
+        println!("{} {}", shape::area(s), shape::perimeter(s));
+    }
+    println!("{}", shape::color_code(shape::Color::Red));
+    println!("{}", shape::color_code(shape::Color::Green));
+}
@@ -0,0 +1,23 @@
+mod list {
+    pub(crate) struct Node {
+        pub(crate) value: u32,
+    }
+
+    impl Node {
+        pub(crate) fn new(value: u32) -> Self {
+            Node { value }
+        }
+    }
+
+    impl std::fmt::Display for Node {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "list node {}", self.value)
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let node = list::Node::new(3);
+    println!("{}", node);
+}
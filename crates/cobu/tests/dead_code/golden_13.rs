@@ -0,0 +1,42 @@
+use std::cmp::Reverse as Rev;
+use std::collections::BTreeMap;
+use std::io::{stdin, BufRead};
+
+mod geometry {
+    pub(crate) mod point {
+        pub(crate) struct Point(pub(crate) i64, pub(crate) i64);
+    }
+
+    pub(crate) mod vector {
+        pub(crate) struct Vector(pub(crate) i64, pub(crate) i64);
+    }
+}
+
+use geometry::{point::Point, vector};
+
+struct Counter(u32);
+
+impl Counter {
+    fn bump(&mut self) {
+        use std::cmp::max;
+        self.0 = max(self.0, 1) + 1;
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    use std::collections::VecDeque;
+    let queue: VecDeque<u32> = VecDeque::new();
+    let mut counter = Counter(queue.len() as u32);
+    counter.bump();
+    let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+    let mut lines = stdin().lock().lines();
+    while let Some(Ok(line)) = lines.next() {
+        *counts.entry(line.len() as u32).or_default() += 1;
+    }
+    let p = Point(1, 2);
+    let v = vector::Vector(3, 4);
+    let mut heap = std::collections::BinaryHeap::new();
+    heap.push(Rev(p.0 + v.1));
+    println!("{:?} {} {}", counts, p.1 + v.0, counter.0);
+}
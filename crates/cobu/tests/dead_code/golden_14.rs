@@ -0,0 +1,18 @@
+mod lib {
+    pub(crate) use self::math::gcd;
+
+    mod math {
+        pub(crate) fn gcd(a: u64, b: u64) -> u64 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::gcd(4, 6));
+}
@@ -0,0 +1,41 @@
+mod num {
+    use std::ops::Add;
+
+    pub(crate) trait One {
+        fn one() -> Self;
+    }
+
+    macro_rules! one_impl {
+        ($t:ty) => {
+            impl One for $t {
+                fn one() -> Self {
+                    1
+                }
+            }
+        };
+    }
+
+    one_impl!(u32);
+    one_impl!(u64);
+
+    pub(crate) trait Double {
+        fn double(self) -> Self;
+    }
+
+    impl<T: Add<Output = T> + Copy> Double for T {
+        fn double(self) -> Self {
+            self + self
+        }
+    }
+
+    pub(crate) fn increment<T: One + Add<Output = T>>(x: T) -> T {
+        x + T::one()
+    }
+}
+
+use num::Double;
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", num::increment(2u32).double());
+}
@@ -0,0 +1,42 @@
+mod lib {
+    pub(crate) trait Bits {
+        fn bits() -> u32;
+    }
+
+    impl Bits for u8 {
+        fn bits() -> u32 {
+            <u8>::BITS
+        }
+    }
+    impl Bits for u16 {
+        fn bits() -> u32 {
+            <u16>::BITS
+        }
+    }
+    impl Bits for u32 {
+        fn bits() -> u32 {
+            <u32>::BITS
+        }
+    }
+    impl Bits for u64 {
+        fn bits() -> u32 {
+            <u64>::BITS
+        }
+    }
+
+    pub(crate) const MOD: u64 = 1_000_000_000 + 7;
+
+    pub(crate) fn square_u64(x: u64) -> u64 {
+        x * x
+    }
+
+    pub(crate) fn bits<T: Bits>() -> u32 {
+        T::bits()
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::square_u64(3) % lib::MOD);
+    println!("{}", lib::bits::<u16>());
+}
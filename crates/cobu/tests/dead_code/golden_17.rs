@@ -0,0 +1,16 @@
+mod lib {
+    macro_rules! square {
+        ($x:expr) => {
+            $x * $x
+        };
+    }
+
+    pub(crate) fn area(side: u64) -> u64 {
+        square!(side)
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::area(3));
+}
@@ -0,0 +1,14 @@
+mod lib {
+    use std::fmt::Write;
+
+    pub(crate) fn render(x: u32) -> String {
+        let mut s = String::new();
+        write!(s, "{x}").unwrap();
+        s
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::render(3));
+}
@@ -1,4 +1,2 @@
-mod mymod {}
-
 #[allow(dead_code)]
 fn main() {}
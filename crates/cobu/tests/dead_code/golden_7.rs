@@ -2,8 +2,3 @@
 fn main() {
     println!("Hello World!");
 }
-
-mod mylib {
-
-    mod nested_lib {}
-}
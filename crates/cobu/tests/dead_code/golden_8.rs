@@ -0,0 +1,26 @@
+fn platform() -> &'static str {
+    "windows"
+}
+
+#[derive(Clone, Copy)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+enum Shape {
+    Dot(Point),
+}
+
+#[allow(dead_code)]
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let Point { x, y } = Point { x: 3, y: 4 };
+    let shape = Shape::Dot(p);
+    match shape {
+        Shape::Dot(p) => println!("{} {} {} {}", p.x, x, y, platform()),
+    }
+    if false {
+        println!("Debug build");
+    }
+}
@@ -0,0 +1,15 @@
+mod shapes {
+
+    #[derive(Clone, Copy)]
+    pub(crate) enum AliveShape {
+        Triangle,
+    }
+
+    pub(crate) const ALIVE_CONST: u32 = 2;
+}
+
+#[allow(dead_code)]
+fn main() {
+    let _shape = shapes::AliveShape::Triangle;
+    println!("{}", shapes::ALIVE_CONST);
+}
@@ -0,0 +1,49 @@
+mod grid {
+    pub struct Grid {
+        inner: Vec<u32>,
+        cols: usize,
+    }
+
+    impl Grid {
+        pub const DEFAULT_COLS: usize = 4;
+
+        pub fn new(rows: usize, cols: usize) -> Grid {
+            Grid {
+                inner: vec![0; rows * cols],
+                cols,
+            }
+        }
+
+        pub fn cols(&self) -> usize {
+            self.cols
+        }
+
+        pub fn rows(&self) -> usize {
+            self.inner.len() / self.cols
+        }
+
+        fn inner_index(&self, index: (usize, usize)) -> usize {
+            index.0 * self.cols + index.1
+        }
+
+        pub fn get(&self, index: (usize, usize)) -> u32 {
+            self.inner[self.inner_index(index)]
+        }
+    }
+
+    impl Grid {
+        pub fn transpose(&self) -> Grid {
+            Grid::new(self.cols(), self.rows())
+        }
+
+        pub fn is_square(&self) -> bool {
+            self.cols() == self.rows()
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let grid = grid::Grid::new(2, 3);
+    println!("{}", grid.get((1, 2)));
+}
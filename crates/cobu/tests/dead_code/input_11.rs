@@ -0,0 +1,75 @@
+mod counter {
+    pub struct Counter {
+        count: u64,
+        step: u64,
+        history: Vec<u64>,
+    }
+
+    impl Counter {
+        pub fn new() -> Self {
+            Self {
+                count: 0,
+                step: 1,
+                history: Vec::new(),
+            }
+        }
+
+        pub fn increment(&mut self) {
+            self.count += self.step;
+        }
+
+        pub fn count(&self) -> u64 {
+            self.count
+        }
+    }
+}
+
+mod shape {
+    pub enum Shape {
+        Circle(u32),
+        Square(u32),
+        Triangle(u32, u32),
+    }
+
+    pub enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+
+    pub fn area(shape: &Shape) -> u32 {
+        match shape {
+            Shape::Circle(r) => 3 * r * r,
+            Shape::Square(a) | Shape::Triangle(a, _) => a * a,
+        }
+    }
+
+    pub fn perimeter(shape: &Shape) -> u32 {
+        match shape {
+            Shape::Circle(r) => 6 * r,
+            Shape::Square(a) => 4 * a,
+            Shape::Triangle(a, b) => 2 * a + b,
+        }
+    }
+
+    pub fn color_code(color: Color) -> u32 {
+        match color {
+            Color::Red => 0,
+            Color::Green => 1,
+            Color::Blue => 2,
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let mut counter = counter::Counter::new();
+    counter.increment();
+    println!("{}", counter.count());
+    let shapes = [shape::Shape::Circle(1), shape::Shape::Triangle(2, 3)];
+    for s in &shapes {
+        println!("{} {}", shape::area(s), shape::perimeter(s));
+    }
+    println!("{}", shape::color_code(shape::Color::Red));
+    println!("{}", shape::color_code(shape::Color::Green));
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+mod list {
+    pub struct Node {
+        pub value: u32,
+    }
+
+    impl Node {
+        pub fn new(value: u32) -> Self {
+            Node { value }
+        }
+    }
+
+    impl std::fmt::Display for Node {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "list node {}", self.value)
+        }
+    }
+}
+
+mod tree {
+    pub struct Node {
+        pub children: Vec<Node>,
+    }
+
+    impl Node {
+        pub fn leaf() -> Self {
+            Node {
+                children: Vec::new(),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Node {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "tree with {} children", self.children.len())
+        }
+    }
+}
+
+pub struct Wrapper<T>(T);
+
+impl<T> fmt::Display for Wrapper<Vec<T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wrapped", self.0.len())
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    let node = list::Node::new(3);
+    println!("{}", node);
+}
@@ -0,0 +1,46 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display as _};
+use std::io::{stdin, BufRead, Write};
+use std::ops::*;
+use std::{cmp::Reverse as Rev, mem};
+
+mod geometry {
+    pub mod point {
+        pub struct Point(pub i64, pub i64);
+    }
+
+    pub mod vector {
+        pub struct Vector(pub i64, pub i64);
+    }
+}
+
+use geometry::{point::Point, vector::{self, Vector}};
+use geometry::point::Point as P;
+
+struct Counter(u32);
+
+impl Counter {
+    fn bump(&mut self) {
+        use std::cmp::max;
+        use std::mem::swap;
+        self.0 = max(self.0, 1) + 1;
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    use std::collections::{HashSet as Set, VecDeque};
+    let queue: VecDeque<u32> = VecDeque::new();
+    let mut counter = Counter(queue.len() as u32);
+    counter.bump();
+    let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+    let mut lines = stdin().lock().lines();
+    while let Some(Ok(line)) = lines.next() {
+        *counts.entry(line.len() as u32).or_default() += 1;
+    }
+    let p = Point(1, 2);
+    let v = vector::Vector(3, 4);
+    let mut heap = std::collections::BinaryHeap::new();
+    heap.push(Rev(p.0 + v.1));
+    println!("{:?} {} {}", counts, p.1 + v.0, counter.0);
+}
@@ -0,0 +1,49 @@
+mod lib {
+    pub use self::iter::Pairs;
+    pub use self::math::{gcd, lcm};
+
+    mod iter {
+        use self::adaptors::PairsAdaptor;
+
+        pub trait Pairs: Iterator + Sized {
+            fn pairs(self) -> PairsAdaptor<Self> {
+                PairsAdaptor { inner: self }
+            }
+        }
+
+        impl<I: Iterator> Pairs for I {}
+
+        mod adaptors {
+            pub struct PairsAdaptor<I> {
+                pub inner: I,
+            }
+
+            impl<I: Iterator> Iterator for PairsAdaptor<I> {
+                type Item = (I::Item, I::Item);
+
+                fn next(&mut self) -> Option<Self::Item> {
+                    Some((self.inner.next()?, self.inner.next()?))
+                }
+            }
+        }
+    }
+
+    mod math {
+        pub fn gcd(a: u64, b: u64) -> u64 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        pub fn lcm(a: u64, b: u64) -> u64 {
+            a / gcd(a, b) * b
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::gcd(4, 6));
+}
@@ -0,0 +1,67 @@
+mod num {
+    use std::ops::Add;
+
+    pub trait One {
+        fn one() -> Self;
+    }
+
+    macro_rules! one_impl {
+        ($t:ty) => {
+            impl One for $t {
+                fn one() -> Self {
+                    1
+                }
+            }
+        };
+    }
+
+    one_impl!(u32);
+    one_impl!(u64);
+
+    pub trait Zero {
+        fn zero() -> Self;
+    }
+
+    macro_rules! zero_impl {
+        ($($t:ty),*) => {
+            $(impl Zero for $t {
+                fn zero() -> Self {
+                    0
+                }
+            })*
+        };
+    }
+
+    zero_impl!(u32, u64);
+
+    pub trait Double {
+        fn double(self) -> Self;
+    }
+
+    impl<T: Add<Output = T> + Copy> Double for T {
+        fn double(self) -> Self {
+            self + self
+        }
+    }
+
+    pub trait Halve {
+        fn halve(self) -> Self;
+    }
+
+    impl Halve for u32 {
+        fn halve(self) -> Self {
+            self / 2
+        }
+    }
+
+    pub fn increment<T: One + Add<Output = T>>(x: T) -> T {
+        x + T::one()
+    }
+}
+
+use num::{Double, Halve, Zero};
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", num::increment(2u32).double());
+}
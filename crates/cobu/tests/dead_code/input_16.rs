@@ -0,0 +1,47 @@
+mod lib {
+    pub trait Bits {
+        fn bits() -> u32;
+    }
+
+    macro_rules! bits_impl {
+        ($($t:ty),+ $(,)?) => {
+            $(impl Bits for $t {
+                fn bits() -> u32 {
+                    <$t>::BITS
+                }
+            })+
+        };
+    }
+
+    bits_impl!(u8, u16, u32, u64,);
+
+    macro_rules! constant {
+        ($name:ident: $t:ty = $value:expr) => {
+            pub const $name: $t = $value;
+        };
+    }
+
+    constant!(MOD: u64 = 1_000_000_000 + 7);
+    constant!(INF: u64 = u64::MAX / 2);
+
+    macro_rules! square_fn {
+        ($vis:vis fn $name:ident($t:ty)) => {
+            $vis fn $name(x: $t) -> $t {
+                x * x
+            }
+        };
+    }
+
+    square_fn!(pub fn square_u64(u64));
+    square_fn!(pub fn square_i64(i64));
+
+    pub fn bits<T: Bits>() -> u32 {
+        T::bits()
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::square_u64(3) % lib::MOD);
+    println!("{}", lib::bits::<u16>());
+}
@@ -0,0 +1,38 @@
+mod lib {
+    macro_rules! square {
+        ($x:expr) => {
+            $x * $x
+        };
+    }
+
+    macro_rules! cube {
+        ($x:expr) => {
+            $x * square!($x)
+        };
+    }
+
+    macro_rules! count {
+        () => {
+            0
+        };
+        ($head:tt $($tail:tt)*) => {
+            1 + count!($($tail)*)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! debug_all {
+        ($($x:expr),*) => {
+            $(eprintln!("{} = {:?}", stringify!($x), $x);)*
+        };
+    }
+
+    pub fn area(side: u64) -> u64 {
+        square!(side)
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::area(3));
+}
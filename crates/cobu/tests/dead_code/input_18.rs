@@ -0,0 +1,26 @@
+mod lib {
+    use std::fmt::Write;
+    use std::mem::swap;
+
+    pub fn render(x: u32) -> String {
+        let mut s = String::new();
+        write!(s, "{x}").unwrap();
+        s
+    }
+
+    pub fn render_to<W: Write>(w: &mut W, x: u32) {
+        write!(w, "{x}").unwrap();
+    }
+
+    pub fn sorted(mut a: u32, mut b: u32) -> (u32, u32) {
+        if a > b {
+            swap(&mut a, &mut b);
+        }
+        (a, b)
+    }
+}
+
+#[allow(dead_code)]
+fn main() {
+    println!("{}", lib::render(3));
+}
@@ -0,0 +1,57 @@
+#[cfg(target_os = "windows")]
+fn platform() -> &'static str {
+    "windows"
+}
+
+#[cfg(not(target_os = "windows"))]
+fn platform() -> &'static str {
+    "other"
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[cfg_attr(not(debug_assertions), derive(Clone, Copy))]
+struct Point {
+    x: i32,
+    #[cfg(target_pointer_width = "16")]
+    z: i32,
+    y: i32,
+}
+
+enum Shape {
+    #[cfg(debug_assertions)]
+    Debug,
+    Dot(Point),
+}
+
+#[allow(dead_code)]
+fn main() {
+    #[cfg(debug_assertions)]
+    println!("Debug build");
+
+    let p = Point {
+        x: 1,
+        #[cfg(target_pointer_width = "16")]
+        z: 3,
+        y: 2,
+    };
+    let Point {
+        x,
+        #[cfg(target_pointer_width = "16")]
+        z: _,
+        y,
+    } = Point {
+        x: 3,
+        #[cfg(target_pointer_width = "16")]
+        z: 3,
+        y: 4,
+    };
+    let shape = Shape::Dot(p);
+    match shape {
+        #[cfg(debug_assertions)]
+        Shape::Debug => println!("debug"),
+        Shape::Dot(p) => println!("{} {} {} {}", p.x, x, y, platform()),
+    }
+    if cfg!(debug_assertions) {
+        println!("Debug build");
+    }
+}
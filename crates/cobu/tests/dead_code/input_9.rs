@@ -0,0 +1,42 @@
+mod shapes {
+    pub enum DeadShape {
+        Circle,
+        Square,
+    }
+
+    impl DeadShape {
+        pub fn sides(&self) -> u32 {
+            match self {
+                DeadShape::Circle => 0,
+                DeadShape::Square => 4,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub enum AliveShape {
+        Triangle,
+    }
+
+    pub union DeadBits {
+        int: u32,
+        float: f32,
+    }
+
+    impl DeadBits {
+        pub fn int(&self) -> u32 {
+            unsafe { self.int }
+        }
+    }
+
+    pub const DEAD_CONST: u32 = 1;
+    pub const ALIVE_CONST: u32 = 2;
+    pub static DEAD_STATIC: u32 = 3;
+    pub type DeadAlias = (usize, Option<usize>);
+}
+
+#[allow(dead_code)]
+fn main() {
+    let _shape = shapes::AliveShape::Triangle;
+    println!("{}", shapes::ALIVE_CONST);
+}
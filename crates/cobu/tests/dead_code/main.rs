@@ -1,5 +1,6 @@
-use std::{io::Write, path::PathBuf};
 use cobu_macros::integration_test;
+use cobu::MinimizeOptions;
+use std::{io::Write, path::PathBuf};
 
 integration_test!(1);
 integration_test!(2);
@@ -8,19 +9,63 @@ integration_test!(4);
 integration_test!(5);
 integration_test!(6);
 integration_test!(7);
+integration_test!(8);
+integration_test!(9);
+integration_test!(10);
+integration_test!(12);
+integration_test!(13);
+integration_test!(14);
+integration_test!(15);
+integration_test!(18);
+
+#[allow(dead_code, unused_imports)]
+mod input_11;
+mod golden_11;
+#[test]
+fn integration_test_11() {
+    let options = MinimizeOptions {
+        remove_dead_fields: true,
+        ..Default::default()
+    };
+    integration_test_with_options(11, &options);
+}
+
+#[allow(dead_code, unused_imports)]
+mod input_16;
+mod golden_16;
+#[test]
+fn integration_test_16() {
+    let options = MinimizeOptions {
+        expand_macros: true,
+        ..Default::default()
+    };
+    integration_test_with_options(16, &options);
+}
+
+#[allow(dead_code, unused_imports, unused_macros)]
+mod input_17;
+mod golden_17;
+#[test]
+fn integration_test_17() {
+    integration_test(17);
+}
 
 fn integration_test(test_index: u32) {
-    // let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    integration_test_with_options(test_index, &Default::default());
+}
+
+fn integration_test_with_options(test_index: u32, options: &MinimizeOptions) {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
     // For some reason if running from a debugger, CARGO_MANIFEST_DIR is not set.
     // As a workaround, uncomment the following line with a hardcoded path, and comment the line above.
-    let manifest_dir = PathBuf::from("/home/robert/GitProjects/cobu/crates/cobu");
+    // let manifest_dir = PathBuf::from("/home/robert/GitProjects/cobu/crates/cobu");
 
     let dead_code_test_dir = manifest_dir.join("tests").join("dead_code");
     let input_path = dead_code_test_dir.join(format!("input_{test_index}.rs"));
     let golden_path = dead_code_test_dir.join(format!("golden_{test_index}.rs"));
     let input_contents = std::fs::read_to_string(input_path).unwrap();
     let golden_contents = std::fs::read_to_string(golden_path).unwrap();
-    let output_contents = cobu::minimize_code(input_contents).unwrap();
+    let output_contents = cobu::minimize_code(input_contents, options).unwrap();
     let output_path = dead_code_test_dir.join(format!("output_{test_index}.rs"));
     std::fs::File::create(output_path)
         .unwrap()
@@ -32,3 +77,84 @@ fn integration_test(test_index: u32) {
         test_index
     );
 }
+
+#[test]
+fn rustc_runs_until_nothing_is_left() {
+    let runs = |src: &str| {
+        let (_, stats) =
+            cobu::remove_dead_code_with_stats(src.to_string(), false, cobu::DEFAULT_EDITION)
+                .unwrap();
+        stats.rustc_runs
+    };
+    assert_eq!(runs("fn main() {}\n"), 1);
+    assert_eq!(runs("fn unused() {}\nfn main() {}\n"), 2);
+}
+
+#[test]
+fn renamed_exported_macros_are_kept() {
+    let src = r#"mod crads {
+    #[macro_export]
+    macro_rules! one {
+        () => {
+            1
+        };
+    }
+
+    #[macro_export]
+    macro_rules! two {
+        () => {
+            2
+        };
+    }
+}
+
+use crate::one as uno;
+
+fn main() {
+    println!("{}", uno!());
+}
+"#;
+    let output = cobu::remove_dead_code(src.to_string(), false).unwrap();
+    assert!(output.contains("macro_rules! one"));
+    assert!(!output.contains("macro_rules! two"));
+}
+
+#[test]
+fn trait_imports_whose_methods_are_called_are_kept() {
+    // Without `A` in scope, `x.name()` would still compile, calling `B::name` on `&x`
+    let src = r#"mod a {
+    pub trait A {
+        fn name(self) -> &'static str;
+    }
+
+    impl A for i32 {
+        fn name(self) -> &'static str {
+            "a"
+        }
+    }
+}
+
+mod b {
+    pub trait B {
+        fn name(self) -> &'static str;
+    }
+
+    impl B for &i32 {
+        fn name(self) -> &'static str {
+            "b"
+        }
+    }
+}
+
+use a::A as ByValue;
+use b::*;
+
+fn main() {
+    let x = 1;
+    println!("{}", x.name());
+    println!("{}", (&x).name());
+}
+"#;
+    let output = cobu::remove_dead_code(src.to_string(), false).unwrap();
+    assert!(output.contains("use a::A as ByValue;"));
+}
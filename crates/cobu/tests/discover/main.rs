@@ -0,0 +1,78 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use cobu::Lib;
+
+fn discover_test_dir() -> PathBuf {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    manifest_dir.join("tests").join("discover")
+}
+
+fn discover(registry_libs: &[&str], vendor_dir: Option<&Path>) -> BTreeMap<String, Lib> {
+    let app_dir = discover_test_dir().join("app");
+    // The app's `.cargo/config.toml` replaces crates.io with the `vendor` directory.
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(app_dir.join("Cargo.toml"))
+        .current_dir(&app_dir)
+        .other_options(vec!["--offline".to_string()])
+        .exec()
+        .unwrap();
+    let package = metadata.root_package().unwrap();
+    let registry_libs: Vec<String> = registry_libs.iter().map(|s| s.to_string()).collect();
+    cobu::discover_libs(&metadata, package, &registry_libs, vendor_dir)
+        .unwrap()
+        .into_iter()
+        .map(|(name, lib)| {
+            let src_path = lib.src_path.canonicalize().unwrap();
+            (name, Lib { src_path, ..lib })
+        })
+        .collect()
+}
+
+fn lib(package_dir: &str, features: &[&str]) -> Lib {
+    Lib {
+        src_path: discover_test_dir()
+            .join(package_dir)
+            .join("src")
+            .join("lib.rs")
+            .canonicalize()
+            .unwrap(),
+        features: features
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<BTreeSet<_>>(),
+    }
+}
+
+#[test]
+fn discover_path_dependencies() {
+    assert_eq!(
+        discover(&[], None),
+        BTreeMap::from([
+            ("lib_a".to_string(), lib("lib_a", &[])),
+            ("bee".to_string(), lib("lib_b", &["fancy"])),
+        ])
+    );
+}
+
+#[test]
+fn discover_registry_dependencies() {
+    let expected = BTreeMap::from([
+        ("lib_a".to_string(), lib("lib_a", &[])),
+        ("bee".to_string(), lib("lib_b", &["fancy"])),
+        (
+            "tiny_registry".to_string(),
+            lib("vendor/tiny-registry", &["default", "extra"]),
+        ),
+    ]);
+    assert_eq!(discover(&["tiny-registry"], None), expected);
+    assert_eq!(
+        discover(
+            &["tiny-registry"],
+            Some(&discover_test_dir().join("vendor"))
+        ),
+        expected
+    );
+}
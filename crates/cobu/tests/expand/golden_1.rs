@@ -0,0 +1,35 @@
+pub use mylib::root;
+
+mod mylib {
+    pub mod a {
+        pub mod nested {
+            pub fn nested() {}
+        }
+
+        pub fn a() {}
+    }
+    mod b {
+        mod e {
+            pub struct E;
+        }
+    }
+
+    mod c {
+        //! Loaded through a `#[path]` attribute.
+
+        mod f {
+            pub fn f() {}
+        }
+    }
+
+    mod inline {
+        pub mod d {
+            pub fn d() {}
+        }
+    }
+
+    pub fn root() {}
+
+    #[cfg(test)]
+    mod tests;
+}
@@ -0,0 +1,39 @@
+pub use mylib::both;
+
+mod mylib {
+    use crate::mylib::a::A;
+    use crate::mylib::{a::helper, b as bee};
+
+    pub mod a {
+        pub(crate) struct A;
+
+        pub(in crate::mylib::a) fn hidden() {}
+
+        pub fn helper() -> crate::mylib::a::A {
+            hidden();
+            crate::mylib::b::new_a()
+        }
+    }
+
+    mod b {
+        pub fn answer() -> u32 {
+            42
+        }
+
+        pub fn new_a() -> super::A {
+            super::A
+        }
+    }
+
+    macro_rules! make_a {
+        () => {
+            $crate::mylib::a::helper()
+        };
+    }
+
+    pub fn both() -> (A, crate::mylib::a::A) {
+        println!("{}", crate::mylib::b::answer());
+        let _ = bee::new_a();
+        (helper(), make_a!())
+    }
+}
@@ -0,0 +1,35 @@
+use alpha::Alpha;
+
+fn main() {
+    Alpha::default().show();
+}
+
+mod beta {
+    #[derive(Default)]
+    pub struct Beta;
+
+    pub fn describe(_: &Beta) -> &'static str {
+        "beta"
+    }
+
+    pub mod nested {
+        pub fn hello() {
+            println!("{}", crate::beta::describe(&crate::beta::Beta));
+        }
+    }
+}
+mod alpha {
+    use crate::beta::Beta;
+
+    #[derive(Default)]
+    pub struct Alpha {
+        beta: Beta,
+    }
+
+    impl Alpha {
+        pub fn show(&self) {
+            println!("{}", crate::beta::describe(&self.beta));
+            crate::beta::nested::hello();
+        }
+    }
+}
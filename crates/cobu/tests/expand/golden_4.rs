@@ -0,0 +1,20 @@
+pub use feat::describe;
+
+mod feat {
+    #[cfg_attr(any(), derive(serde::Serialize))]
+    pub struct Point(i32, i32);
+
+    #[cfg(all())]
+    pub fn describe() -> String {
+        if cfg!(all(all(), not(any()))) {
+            "fast".to_string()
+        } else {
+            "slow".to_string()
+        }
+    }
+
+    #[cfg(not(all()))]
+    pub fn describe() -> &'static str {
+        "no_std"
+    }
+}
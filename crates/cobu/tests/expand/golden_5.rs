@@ -0,0 +1,38 @@
+use dbg::fourth;
+
+fn main() {
+    println!("{} {}", square!(3), fourth(2));
+    println!("{}", crate::cube!(2));
+    println!("{}", geo::volume(2));
+}
+
+mod dbg {
+    #[macro_export]
+    macro_rules! square {
+        ($x:expr) => {
+            $crate::dbg::mul($x, $x)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! cube {
+        ($x:expr) => {
+            $crate::dbg::mul($x, $crate::square!($x))
+        };
+    }
+
+    pub fn mul(a: i64, b: i64) -> i64 {
+        a * b
+    }
+
+    pub fn fourth(x: i64) -> i64 {
+        crate::square!(crate::square!(x))
+    }
+}
+mod geo {
+    use crate::cube;
+
+    pub fn volume(side: i64) -> i64 {
+        cube!(side)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod nested;
+
+pub fn a() {}
@@ -0,0 +1,14 @@
+pub mod a;
+mod b;
+
+#[path = "other/c_impl.rs"]
+mod c;
+
+mod inline {
+    pub mod d;
+}
+
+pub fn root() {}
+
+#[cfg(test)]
+mod tests;
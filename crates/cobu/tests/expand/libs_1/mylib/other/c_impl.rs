@@ -0,0 +1,3 @@
+//! Loaded through a `#[path]` attribute.
+
+mod f;
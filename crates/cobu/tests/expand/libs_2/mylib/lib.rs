@@ -0,0 +1,35 @@
+use crate::a::A;
+use crate::{a::helper, b as bee};
+
+pub mod a {
+    pub(crate) struct A;
+
+    pub(in crate::a) fn hidden() {}
+
+    pub fn helper() -> crate::a::A {
+        hidden();
+        crate::b::new_a()
+    }
+}
+
+mod b {
+    pub fn answer() -> u32 {
+        42
+    }
+
+    pub fn new_a() -> super::A {
+        super::A
+    }
+}
+
+macro_rules! make_a {
+    () => {
+        $crate::a::helper()
+    };
+}
+
+pub fn both() -> (A, crate::a::A) {
+    println!("{}", crate::b::answer());
+    let _ = bee::new_a();
+    (helper(), make_a!())
+}
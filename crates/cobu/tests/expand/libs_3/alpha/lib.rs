@@ -0,0 +1,13 @@
+use beta::Beta;
+
+#[derive(Default)]
+pub struct Alpha {
+    beta: Beta,
+}
+
+impl Alpha {
+    pub fn show(&self) {
+        println!("{}", beta::describe(&self.beta));
+        ::beta::nested::hello();
+    }
+}
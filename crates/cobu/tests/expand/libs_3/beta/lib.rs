@@ -0,0 +1,12 @@
+#[derive(Default)]
+pub struct Beta;
+
+pub fn describe(_: &Beta) -> &'static str {
+    "beta"
+}
+
+pub mod nested {
+    pub fn hello() {
+        println!("{}", crate::describe(&crate::Beta));
+    }
+}
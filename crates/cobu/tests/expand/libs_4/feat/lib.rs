@@ -0,0 +1,16 @@
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Point(i32, i32);
+
+#[cfg(feature = "std")]
+pub fn describe() -> String {
+    if cfg!(all(feature = "fast", not(feature = "serde"))) {
+        "fast".to_string()
+    } else {
+        "slow".to_string()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub fn describe() -> &'static str {
+    "no_std"
+}
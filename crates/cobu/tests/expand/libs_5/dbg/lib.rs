@@ -0,0 +1,21 @@
+#[macro_export]
+macro_rules! square {
+    ($x:expr) => {
+        $crate::mul($x, $x)
+    };
+}
+
+#[macro_export]
+macro_rules! cube {
+    ($x:expr) => {
+        $crate::mul($x, $crate::square!($x))
+    };
+}
+
+pub fn mul(a: i64, b: i64) -> i64 {
+    a * b
+}
+
+pub fn fourth(x: i64) -> i64 {
+    crate::square!(crate::square!(x))
+}
@@ -0,0 +1,5 @@
+use dbg::cube;
+
+pub fn volume(side: i64) -> i64 {
+    cube!(side)
+}
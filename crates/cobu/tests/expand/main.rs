@@ -0,0 +1,64 @@
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use cobu::Lib;
+
+#[test]
+fn expand_test_1() {
+    expand_test(1);
+}
+
+#[test]
+fn expand_test_2() {
+    expand_test(2);
+}
+
+#[test]
+fn expand_test_3() {
+    expand_test(3);
+}
+
+#[test]
+fn expand_test_4() {
+    expand_test(4);
+}
+
+#[test]
+fn expand_test_5() {
+    expand_test(5);
+}
+
+fn expand_test(test_index: u32) {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+
+    let expand_test_dir = manifest_dir.join("tests").join("expand");
+    let main_path = expand_test_dir.join(format!("main_{test_index}.rs"));
+    let libs_dir = expand_test_dir.join(format!("libs_{test_index}"));
+    let golden_path = expand_test_dir.join(format!("golden_{test_index}.rs"));
+    let main_contents = std::fs::read_to_string(main_path).unwrap();
+    let golden_contents = std::fs::read_to_string(golden_path).unwrap();
+    let libs: BTreeMap<String, Lib> = std::fs::read_dir(libs_dir)
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            let name = entry.file_name().into_string().unwrap();
+            let mut lib = Lib::new(entry.path().join("lib.rs"));
+            // Enabled features are listed one per line next to the library root.
+            if let Ok(features) = std::fs::read_to_string(entry.path().join("features")) {
+                lib.features = features.lines().map(String::from).collect();
+            }
+            (name, lib)
+        })
+        .collect();
+    let output_contents = cobu::expand_libs(&libs, main_contents).unwrap();
+    let output_contents = cobu::rustfmt(&output_contents, cobu::DEFAULT_EDITION).unwrap();
+    let output_path = expand_test_dir.join(format!("output_{test_index}.rs"));
+    std::fs::File::create(output_path)
+        .unwrap()
+        .write_all(output_contents.as_bytes())
+        .unwrap();
+    assert_eq!(
+        output_contents, golden_contents,
+        "Expand test #{} failed",
+        test_index
+    );
+}
@@ -0,0 +1 @@
+pub use mylib::both;
@@ -0,0 +1,5 @@
+use alpha::Alpha;
+
+fn main() {
+    Alpha::default().show();
+}
@@ -0,0 +1,7 @@
+use dbg::{fourth, square};
+
+fn main() {
+    println!("{} {}", square!(3), fourth(2));
+    println!("{}", dbg::cube!(2));
+    println!("{}", geo::volume(2));
+}
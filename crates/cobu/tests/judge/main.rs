@@ -0,0 +1,32 @@
+use std::str::FromStr;
+
+use cobu::Cfg;
+
+#[test]
+fn package_metadata_overrides_judge() {
+    let metadata = serde_json::json!({
+        "cobu": {
+            "judge": "codeforces",
+            "size-limit": 32768,
+            "cfg": ["target_os = \"linux\""],
+        }
+    });
+    let profile = cobu::package_judge_profile(&metadata, None).unwrap();
+    assert_eq!(profile.name, "codeforces");
+    assert_eq!(profile.rust_version.as_deref(), Some("1.75.0"));
+    assert_eq!(profile.size_limit, Some(32768));
+    assert!(profile
+        .target_cfg
+        .contains(&Cfg::from_str("target_os = \"linux\"").unwrap()));
+    assert!(!profile
+        .target_cfg
+        .contains(&Cfg::from_str("target_os = \"windows\"").unwrap()));
+}
+
+#[test]
+fn cli_judge_takes_precedence() {
+    let metadata = serde_json::json!({ "cobu": { "judge": "codeforces" } });
+    let profile = cobu::package_judge_profile(&metadata, Some("atcoder")).unwrap();
+    assert_eq!(profile, cobu::judge_profile("atcoder").unwrap());
+    assert!(cobu::package_judge_profile(&serde_json::Value::Null, Some("unknown")).is_err());
+}
@@ -0,0 +1,86 @@
+#[test]
+fn minify_removes_docs_comments_and_cosmetic_attributes() {
+    let src = r#"//! Crate docs
+use std::io::Read; // Needed for read_to_string
+
+/// Doubles a number.
+///
+/// ```
+/// assert_eq!(double(2), 4);
+/// ```
+#[must_use]
+#[inline(always)]
+fn double(x: u64) -> u64 {
+    /* multiply */
+    x * 2
+}
+
+#[track_caller]
+fn check(x: u64) {
+    assert!(x > 0);
+}
+
+#[derive(Debug, Clone)]
+struct Point {
+    /// Horizontal
+    x: i32,
+}
+"#;
+    assert_eq!(
+        cobu::minify(src.to_string()).unwrap(),
+        "use std :: io :: Read ;\n\
+         fn double (x : u64) -> u64 { x * 2 }\n\
+         # [track_caller] fn check (x : u64) { assert ! (x > 0) ; }\n\
+         # [derive (Debug , Clone)] struct Point { x : i32 , }\n"
+    );
+}
+
+#[test]
+fn remove_lib_docs_keeps_binary_comments() {
+    let src = r#"// Solution
+fn main() {
+    /// Count
+    let n = crads::one(); // Always one
+}
+mod crads {
+    //! Library docs
+    /// Returns one.
+    #[must_use = "it's one"]
+    pub(crate) fn one() -> u32 {
+        1 /* one */
+    }
+    pub(crate) const URL: &str = "https://example.com"; // Not a comment in the string
+    macro_rules! m {
+        () => {
+            /// Generated
+            struct S;
+        };
+    }
+}
+// Still the binary's
+fn after() {} /* kept */
+"#;
+    let libs = ["crads".to_string()].into_iter().collect();
+    assert_eq!(
+        cobu::remove_lib_docs(src.to_string(), &libs).unwrap(),
+        r#"// Solution
+fn main() {
+    /// Count
+    let n = crads::one(); // Always one
+}
+mod crads {
+pub(crate) fn one() -> u32 {
+        1
+}
+    pub(crate) const URL: &str = "https://example.com";
+macro_rules! m {
+        () => {
+struct S;
+        };
+    }
+}
+// Still the binary's
+fn after() {} /* kept */
+"#
+    );
+}
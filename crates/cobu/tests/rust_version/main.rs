@@ -0,0 +1,67 @@
+fn newer_features(src: &str, rust_version: &str) -> Vec<(usize, String, &'static str)> {
+    cobu::newer_features(src, rust_version)
+        .unwrap()
+        .into_iter()
+        .map(|f| (f.span.start().line, f.description, f.version))
+        .collect()
+}
+
+#[test]
+fn reports_features_newer_than_judge() {
+    let src = r#"use std::iter::repeat_n;
+
+fn main() {
+    let v = vec![3, 1, 2];
+    let Some(&x) = v.first() else { return };
+    println!("{}", v.is_sorted());
+    let n: u64 = 10;
+    assert_eq!(n.div_ceil(3), 4);
+    if let Some(y) = v.last() && *y > x {}
+}
+"#;
+    assert_eq!(
+        newer_features(src, "1.70.0"),
+        vec![
+            (1, "`std::iter::repeat_n`".to_string(), "1.82.0"),
+            (6, "`.is_sorted()`".to_string(), "1.82.0"),
+            (8, "`.div_ceil()`".to_string(), "1.73.0"),
+            (9, "a `let` chain".to_string(), "1.88.0"),
+        ]
+    );
+    assert_eq!(
+        newer_features(src, "1.64").len(),
+        5,
+        "`let ... else` is newer too"
+    );
+    assert!(newer_features(src, "1.88.0").is_empty());
+}
+
+#[test]
+fn local_definitions_shadow_std() {
+    let src = r#"trait DivCeil {
+    fn div_ceil(self, d: u64) -> u64;
+}
+
+fn main() {
+    let _ = 7u64.div_ceil(2);
+}
+"#;
+    assert!(newer_features(src, "1.60.0").is_empty());
+}
+
+#[test]
+fn methods_of_external_traits_are_possible_matches() {
+    let src = r#"use num_integer::Integer;
+
+fn main() {
+    let _ = 7u64.div_ceil(&2);
+    let _ = std::iter::repeat_n(0, 3);
+}
+"#;
+    let features: Vec<(usize, bool)> = cobu::newer_features(src, "1.70.0")
+        .unwrap()
+        .into_iter()
+        .map(|f| (f.span.start().line, f.is_possible_match))
+        .collect();
+    assert_eq!(features, vec![(4, true), (5, false)]);
+}
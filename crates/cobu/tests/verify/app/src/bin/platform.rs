@@ -0,0 +1,4 @@
+fn main() {
+    let platform = if cfg!(windows) { "windows" } else { "unix" };
+    println!("{platform}");
+}
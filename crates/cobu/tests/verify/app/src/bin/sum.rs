@@ -0,0 +1,11 @@
+use std::io::Read;
+
+fn main() {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).unwrap();
+    let sum: u64 = input
+        .split_whitespace()
+        .map(|s| s.parse::<u64>().unwrap())
+        .sum();
+    println!("{sum}");
+}
@@ -0,0 +1,47 @@
+#[path = "../common/mod.rs"]
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+fn verify(dir: &Path, tests_dir: &str, cfgs: &[&str]) -> anyhow::Result<()> {
+    cobu::cli(cobu::Args {
+        manifest_path: Some(dir.join("Cargo.toml")),
+        out_dir: dir.join("dist"),
+        offline: true,
+        cfgs: cfgs
+            .iter()
+            .map(|s| cobu::Cfg::from_str(s).unwrap())
+            .collect(),
+        command: Some(cobu::Command::Verify {
+            tests_dir: PathBuf::from(tests_dir),
+        }),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn matching_bundles_pass() {
+    let dir = common::fixture("verify", &["app"], "matching").join("app");
+    verify(&dir, "tests", &["target_family = \"unix\""]).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn every_mismatching_input_is_reported() {
+    let dir = common::fixture("verify", &["app"], "mismatching").join("app");
+    // The bundle is made for Windows, so it doesn't print what the original does here.
+    let error = format!("{:#}", verify(&dir, "bad_tests", &[]).unwrap_err());
+    let input = |bin: &str, name: &str| dir.join("bad_tests").join(bin).join(name);
+    assert!(error.contains(&format!(
+        "{}: line 1: original printed \"unix\", bundle printed \"windows\"",
+        input("platform", "1.in").display()
+    )));
+    assert!(!error.contains(&input("sum", "1.in").display().to_string()));
+    assert!(error.contains(&format!(
+        "{}: original failed: ",
+        input("sum", "2.in").display()
+    )));
+}
@@ -21,8 +21,7 @@ pub fn primes(n: u32) -> Vec<u32> {
             is_prime[usize::try_from(y).unwrap()] = false;
         }
     }
-    let primes = (0..=n).filter(|x| is_prime[usize::try_from(*x).unwrap()]).collect();
-    primes
+    (0..=n).filter(|x| is_prime[usize::try_from(*x).unwrap()]).collect()
 }
 
 
@@ -30,6 +29,7 @@ pub fn prime_factors(mut n: u32, primes: &[u32]) -> Vec<u32> {
     assert!(primes.last().unwrap().saturating_mul(*primes.last().unwrap()) >= n, "{} > {:#?}^2 = {:#?}", n, primes.last(), primes.last().map(|x| x.saturating_mul(*x)));
     let mut factors = Vec::new();
     for p in primes {
+        #[allow(clippy::manual_is_multiple_of)]
         while n % p == 0 {
             factors.push(*p);
             n /= p;
@@ -221,7 +221,6 @@ impl Scanner {
     }
 }
 
-#[allow(dead_code)]
 pub trait One {
     fn one() -> Self;
 }
@@ -247,7 +246,6 @@ one_impl!(u32);
 one_impl!(u64);
 one_impl!(u128);
 
-#[allow(dead_code)]
 pub trait Zero {
     fn zero() -> Self;
 }
@@ -378,7 +376,7 @@ pub fn iter_all_vectors<T: PartialOrd + One + Add<Output = T> + Clone>(
 pub fn sorted<I: IntoIterator<Item = T>, T: Ord>(iter: I) -> bool {
     let mut iter = iter.into_iter();
     if let Some(mut prev) = iter.next() {
-        while let Some(curr) = iter.next() {
+        for curr in iter {
             if prev > curr {
                 return false;
             }